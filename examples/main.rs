@@ -45,6 +45,18 @@ fn main() {
         .println();
     }
 
+    // Set the color support override to no color mode.
+    {
+        color_support_override_set(ColorSupportOverride::NoColor);
+        print_text("> Force no color mode");
+    }
+
+    // Set the color support override to ANSI 16 color mode.
+    {
+        color_support_override_set(ColorSupportOverride::Ansi16);
+        print_text("> Force ANSI 16 color mode");
+    }
+
     // Set the color support override to ANSI 256 color mode.
     {
         color_support_override_set(ColorSupportOverride::Ansi256);
@@ -60,9 +72,10 @@ fn main() {
     // Use runtime detection to determine the color support.
     {
         color_support_override_set(ColorSupportOverride::NotSet);
+        let detection = detect_color_support(Stream::Stdout);
         let msg = format!(
-            "> Runtime detection of color support ({:?})",
-            supports_color(Stream::Stdout)
+            "> Runtime detection of color support ({:?}, {})",
+            detection.color_support, detection.rule
         );
         print_text(&msg);
     }
@@ -96,4 +109,13 @@ fn print_text(msg: &str) {
         ],
     };
     println!("eg_2: {0}", eg_2);
+
+    let gradient = Gradient::new([Color::Rgb(200, 200, 1), Color::Rgb(100, 60, 150)]);
+    gradient
+        .render(
+            "eg_3: gradient text",
+            GradientTarget::Foreground,
+            query_runtime_color_support(Stream::Stdout),
+        )
+        .println();
 }
@@ -0,0 +1,212 @@
+/*
+ *   Copyright (c) 2023 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+//! A small lock free cache for RGB to ANSI 256 conversions. Rendering logs and tables
+//! tends to use the same handful of colors over and over again, so each distinct color
+//! is converted only once (until it is evicted by another color w/ the same slot).
+//!
+//! The cache is direct mapped, and each slot is a single [AtomicU64] that packs the
+//! whole key (RGB + [Ansi256ConversionStrategy]) and the palette generation next to the
+//! result. So a lookup can never return the result for a different color, or for a
+//! palette that has since been replaced w/ [palette_set](crate::palette_set), and the
+//! cached results are always identical to calling [convert_rgb_into_ansi256_using]
+//! directly.
+
+use crate::{convert_rgb_into_ansi256_using, Ansi256Color, Ansi256ConversionStrategy, RgbColor};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Must be a power of 2. 4096 slots take up 32KB.
+const CACHE_SIZE: usize = 4096;
+
+/// Marks a slot that holds a result (a zeroed slot is empty).
+const VALID_BIT: u64 = 1 << 63;
+
+/// The bits of the palette generation that are stored in a slot. The rest of the 63 bits
+/// hold the key (26 bits) and the result (8 bits).
+const GENERATION_MASK: u64 = (1 << 29) - 1;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_SLOT: AtomicU64 = AtomicU64::new(0);
+
+static CACHE: [AtomicU64; CACHE_SIZE] = [EMPTY_SLOT; CACHE_SIZE];
+
+/// Incremented by [ansi256_conversion_cache_clear]. A result that was converted w/ an
+/// older palette (eg: by another thread, while [palette_set](crate::palette_set) was
+/// running) is stored w/ an older generation, so it is never returned.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Same as [convert_rgb_into_ansi256_using], but the result is cached.
+pub fn convert_rgb_into_ansi256_cached(
+    rgb_color: RgbColor,
+    strategy: Ansi256ConversionStrategy,
+) -> Ansi256Color {
+    let key = cache_key(rgb_color, strategy);
+    let slot = &CACHE[cache_slot_index(key)];
+
+    // This has to be read before the palette is, see [GENERATION].
+    let tag = cache_tag(key, GENERATION.load(Ordering::Acquire));
+    if let Some(result) = cached_result(slot, tag) {
+        return result;
+    }
+
+    let result = convert_rgb_into_ansi256_using(rgb_color, strategy);
+    slot.store(
+        VALID_BIT | tag << 8 | result.index as u64,
+        Ordering::Relaxed,
+    );
+    result
+}
+
+/// Removes all the cached results. This is called by [palette_set](crate::palette_set).
+pub fn ansi256_conversion_cache_clear() {
+    GENERATION.fetch_add(1, Ordering::Release);
+    for slot in CACHE.iter() {
+        slot.store(0, Ordering::Relaxed);
+    }
+}
+
+/// 24 bits of RGB, and 2 bits for the strategy.
+fn cache_key(rgb_color: RgbColor, strategy: Ansi256ConversionStrategy) -> u64 {
+    let RgbColor { red, green, blue } = rgb_color;
+    let strategy: u8 = strategy.into();
+    (strategy as u64) << 24 | (red as u64) << 16 | (green as u64) << 8 | blue as u64
+}
+
+/// The key, w/ the (truncated) palette generation above it.
+fn cache_tag(key: u64, generation: u64) -> u64 {
+    (generation & GENERATION_MASK) << 26 | key
+}
+
+fn cached_result(slot: &AtomicU64, tag: u64) -> Option<Ansi256Color> {
+    let entry = slot.load(Ordering::Relaxed);
+    (entry & VALID_BIT != 0 && (entry & !VALID_BIT) >> 8 == tag)
+        .then_some(Ansi256Color { index: entry as u8 })
+}
+
+/// Fibonacci hashing, so that similar colors don't collide.
+fn cache_slot_index(key: u64) -> usize {
+    (key.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> (64 - CACHE_SIZE.trailing_zeros())) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn assert_cached_result_is_identical(rgb_color: RgbColor, strategy: Ansi256ConversionStrategy) {
+        let expected = convert_rgb_into_ansi256_using(rgb_color, strategy);
+        // The first call might be a miss, the second one a hit.
+        assert_eq!(
+            convert_rgb_into_ansi256_cached(rgb_color, strategy),
+            expected
+        );
+        assert_eq!(
+            convert_rgb_into_ansi256_cached(rgb_color, strategy),
+            expected
+        );
+    }
+
+    /// The values of a color channel around which the cube index changes: the levels of
+    /// the 6×6×6 cube, and the values right next to the midpoints between them.
+    fn cube_boundary_values() -> Vec<u8> {
+        let levels = [0u8, 95, 135, 175, 215, 255];
+        let mut acc = levels.to_vec();
+        for pair in levels.windows(2) {
+            let mid = pair[0] + (pair[1] - pair[0]) / 2;
+            acc.extend([mid - 1, mid, mid + 1]);
+        }
+        acc
+    }
+
+    #[test]
+    fn test_cached_results_are_identical() {
+        let values = cube_boundary_values();
+        for &red in &values {
+            for &green in &values {
+                for &blue in &values {
+                    let rgb_color = RgbColor { red, green, blue };
+                    assert_cached_result_is_identical(
+                        rgb_color,
+                        Ansi256ConversionStrategy::Redmean,
+                    );
+                }
+            }
+        }
+        // Every gray, and the colors right next to it, which covers the gray boundaries.
+        for value in 0..=255u8 {
+            let other = value.saturating_add(1);
+            for (red, green, blue) in [
+                (value, value, value),
+                (value, value, other),
+                (other, value, value),
+            ] {
+                assert_cached_result_is_identical(
+                    RgbColor { red, green, blue },
+                    Ansi256ConversionStrategy::Redmean,
+                );
+            }
+        }
+
+        // The perceptual strategies search the whole palette, which is slow.
+        for red in (0..=255).step_by(15) {
+            for green in (0..=255).step_by(15) {
+                for blue in (0..=255).step_by(15) {
+                    assert_cached_result_is_identical(
+                        RgbColor { red, green, blue },
+                        Ansi256ConversionStrategy::Oklab,
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_stale_results_are_not_returned() {
+        let rgb_color = RgbColor::from(0x123456);
+        let key = cache_key(rgb_color, Ansi256ConversionStrategy::Redmean);
+        let slot = &CACHE[cache_slot_index(key)];
+
+        // A result that was stored while the palette was being replaced.
+        let stale_tag = cache_tag(key, GENERATION.load(Ordering::Acquire));
+        ansi256_conversion_cache_clear();
+        slot.store(VALID_BIT | stale_tag << 8 | 42, Ordering::Relaxed);
+
+        let tag = cache_tag(key, GENERATION.load(Ordering::Acquire));
+        assert_eq!(cached_result(slot, tag), None);
+        assert_eq!(
+            convert_rgb_into_ansi256_cached(rgb_color, Ansi256ConversionStrategy::Redmean),
+            convert_rgb_into_ansi256_using(rgb_color, Ansi256ConversionStrategy::Redmean)
+        );
+    }
+
+    #[test]
+    fn test_strategy_is_part_of_the_key() {
+        let rgb_color = RgbColor::from(0x110033);
+        assert_eq!(
+            convert_rgb_into_ansi256_cached(rgb_color, Ansi256ConversionStrategy::Redmean),
+            Ansi256Color { index: 233 }
+        );
+        assert_eq!(
+            convert_rgb_into_ansi256_cached(rgb_color, Ansi256ConversionStrategy::Oklab),
+            Ansi256Color { index: 17 }
+        );
+        assert_eq!(
+            convert_rgb_into_ansi256_cached(rgb_color, Ansi256ConversionStrategy::Redmean),
+            Ansi256Color { index: 233 }
+        );
+    }
+}
@@ -19,7 +19,7 @@
 //! - <https://doc.rust-lang.org/reference/tokens.html#ascii-escapes>
 //! - <https://notes.burke.libbey.me/ansi-escape-codes/>
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum SgrCode {
     Reset,
     Bold,
@@ -32,6 +32,24 @@ pub enum SgrCode {
     Invert,
     Hidden,
     Strikethrough,
+    /// Turns off both [SgrCode::Bold] and [SgrCode::Dim] (normal intensity).
+    ResetBoldDim,
+    ResetItalic,
+    ResetUnderline,
+    /// Turns off both [SgrCode::SlowBlink] and [SgrCode::RapidBlink].
+    ResetBlink,
+    ResetInvert,
+    ResetHidden,
+    ResetStrikethrough,
+    ResetOverline,
+    /// Sets the foreground color back to the terminal's default.
+    ResetForeground,
+    /// Sets the background color back to the terminal's default.
+    ResetBackground,
+    /// Index into the 16 system colors (`0..=15`).
+    ForegroundAnsi16(u8),
+    /// Index into the 16 system colors (`0..=15`).
+    BackgroundAnsi16(u8),
     ForegroundAnsi256(u8),
     BackgroundAnsi256(u8),
     ForegroundRGB(u8, u8, u8),
@@ -70,13 +88,37 @@ pub mod sgr_code_impl {
             SgrCode::Invert            => format!("{CSI}7{SGR}"),
             SgrCode::Hidden            => format!("{CSI}8{SGR}"),
             SgrCode::Strikethrough     => format!("{CSI}9{SGR}"),
-            SgrCode::Overline           => format!("{CSI}53{SGR}"),
+            SgrCode::Overline          => format!("{CSI}53{SGR}"),
+            SgrCode::ResetBoldDim      => format!("{CSI}22{SGR}"),
+            SgrCode::ResetItalic       => format!("{CSI}23{SGR}"),
+            SgrCode::ResetUnderline    => format!("{CSI}24{SGR}"),
+            SgrCode::ResetBlink        => format!("{CSI}25{SGR}"),
+            SgrCode::ResetInvert       => format!("{CSI}27{SGR}"),
+            SgrCode::ResetHidden       => format!("{CSI}28{SGR}"),
+            SgrCode::ResetStrikethrough => format!("{CSI}29{SGR}"),
+            SgrCode::ResetOverline     => format!("{CSI}55{SGR}"),
+            SgrCode::ResetForeground   => format!("{CSI}39{SGR}"),
+            SgrCode::ResetBackground   => format!("{CSI}49{SGR}"),
+            SgrCode::ForegroundAnsi16(index)  => format!("{CSI}{}{SGR}", ansi16_sgr_param(index, 30, 90)),
+            SgrCode::BackgroundAnsi16(index)  => format!("{CSI}{}{SGR}", ansi16_sgr_param(index, 40, 100)),
             SgrCode::ForegroundAnsi256(index) => format!("{CSI}38;5;{index}{SGR}"),
             SgrCode::BackgroundAnsi256(index) => format!("{CSI}48;5;{index}{SGR}"),
             SgrCode::ForegroundRGB(r, g, b) => format!("{CSI}38;2;{r};{g};{b}{SGR}"),
             SgrCode::BackgroundRGB(r, g, b) => format!("{CSI}48;2;{r};{g};{b}{SGR}"),
         }
     }
+
+    /// The first 8 system colors map to `normal_base + index` (eg: 30–37 or 40–47), and the
+    /// bright ones map to `bright_base + index - 8` (eg: 90–97 or 100–107). Indices above
+    /// 15 are clamped, like [Ansi16Color::new](crate::Ansi16Color::new) does.
+    fn ansi16_sgr_param(index: u8, normal_base: u8, bright_base: u8) -> u8 {
+        let index = index.min(15);
+        if index < 8 {
+            normal_base + index
+        } else {
+            bright_base + index - 8
+        }
+    }
 }
 
 #[cfg(test)]
@@ -96,6 +138,13 @@ mod tests {
         assert_eq!(sgr_code.to_string(), "\x1b[0m");
     }
 
+    #[test]
+    fn reset_attributes() {
+        assert_eq!(SgrCode::ResetBoldDim.to_string(), "\x1b[22m");
+        assert_eq!(SgrCode::ResetForeground.to_string(), "\x1b[39m");
+        assert_eq!(SgrCode::ResetBackground.to_string(), "\x1b[49m");
+    }
+
     #[test]
     fn fg_color_ansi256() {
         let sgr_code = SgrCode::ForegroundAnsi256(150);
@@ -108,6 +157,23 @@ mod tests {
         assert_eq!(sgr_code.to_string(), "\x1b[48;5;150m");
     }
 
+    #[test]
+    fn fg_color_ansi16() {
+        assert_eq!(SgrCode::ForegroundAnsi16(0).to_string(), "\x1b[30m");
+        assert_eq!(SgrCode::ForegroundAnsi16(7).to_string(), "\x1b[37m");
+        assert_eq!(SgrCode::ForegroundAnsi16(8).to_string(), "\x1b[90m");
+        assert_eq!(SgrCode::ForegroundAnsi16(15).to_string(), "\x1b[97m");
+        assert_eq!(SgrCode::ForegroundAnsi16(16).to_string(), "\x1b[97m");
+    }
+
+    #[test]
+    fn bg_color_ansi16() {
+        assert_eq!(SgrCode::BackgroundAnsi16(0).to_string(), "\x1b[40m");
+        assert_eq!(SgrCode::BackgroundAnsi16(7).to_string(), "\x1b[47m");
+        assert_eq!(SgrCode::BackgroundAnsi16(8).to_string(), "\x1b[100m");
+        assert_eq!(SgrCode::BackgroundAnsi16(15).to_string(), "\x1b[107m");
+    }
+
     #[test]
     fn fg_color_rgb() {
         let sgr_code = SgrCode::ForegroundRGB(175, 215, 135);
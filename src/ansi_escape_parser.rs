@@ -0,0 +1,758 @@
+/*
+ *   Copyright (c) 2023 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+//! Parse ANSI escape sequences (that are generated by this crate, or by other programs)
+//! back into [SgrCode]s and [StyledSpan]s.
+//!
+//! There are 3 layers:
+//! 1. [recognize_escape] - finds the extent of a single escape sequence in a byte stream.
+//!    This is shared by everything in this crate that has to deal w/ escape sequences.
+//! 2. [tokenize] - splits a string into [AnsiToken]s (text runs and [SgrCode]s).
+//! 3. [parse_styled_spans] - applies the [SgrCode]s to build a list of [StyledSpan]s,
+//!    which can be converted into [OwnedStyledText](crate::OwnedStyledText) to render
+//!    them again.
+//!
+//! More info:
+//! - <https://en.wikipedia.org/wiki/ANSI_escape_code#Control_Sequence_Introducer_commands>
+//! - <https://vt100.net/emu/dec_ansi_parser>
+
+use crate::{Color, SgrCode, Style};
+
+pub const ESC: u8 = 0x1b;
+pub const BEL: u8 = 0x07;
+
+/// A single escape sequence, borrowed from the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EscapeSequence<'a> {
+    /// Control Sequence Introducer: `ESC [ <params> <intermediates> <final_byte>`. SGR
+    /// sequences are CSI sequences w/ a final byte of `m`.
+    Csi {
+        params: &'a [u8],
+        intermediates: &'a [u8],
+        final_byte: u8,
+    },
+    /// Operating System Command: `ESC ] <payload> (BEL | ESC \)`.
+    Osc { payload: &'a [u8] },
+    /// Device Control String (and SOS, PM, APC): `ESC (P | X | ^ | _) <payload> ESC \`.
+    Dcs { kind: u8, payload: &'a [u8] },
+    /// Any other escape, eg: `ESC 7`, `ESC ( B`.
+    Other { bytes: &'a [u8] },
+}
+
+/// The result of running [recognize_escape] on a byte stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recognized<'a> {
+    /// A complete escape sequence that is `len` bytes long.
+    Complete {
+        sequence: EscapeSequence<'a>,
+        len: usize,
+    },
+    /// The input ends before the escape sequence is terminated. More bytes are needed.
+    Incomplete,
+    /// The first `len` bytes are not a valid escape sequence.
+    Invalid { len: usize },
+}
+
+/// Recognizes the escape sequence at the start of `input`, which must start w/ [ESC].
+pub fn recognize_escape(input: &[u8]) -> Recognized<'_> {
+    debug_assert_eq!(input.first(), Some(&ESC));
+    let Some(&introducer) = input.get(1) else {
+        return Recognized::Incomplete;
+    };
+
+    match introducer {
+        b'[' => recognize_csi(input),
+        b']' => match find_string_terminator(input) {
+            Some((payload_end, len)) => Recognized::Complete {
+                sequence: EscapeSequence::Osc {
+                    payload: &input[2..payload_end],
+                },
+                len,
+            },
+            None => Recognized::Incomplete,
+        },
+        b'P' | b'X' | b'^' | b'_' => match find_string_terminator(input) {
+            Some((payload_end, len)) => Recognized::Complete {
+                sequence: EscapeSequence::Dcs {
+                    kind: introducer,
+                    payload: &input[2..payload_end],
+                },
+                len,
+            },
+            None => Recognized::Incomplete,
+        },
+        _ => recognize_other(input),
+    }
+}
+
+fn recognize_csi(input: &[u8]) -> Recognized<'_> {
+    let params_start = 2;
+    let mut index = params_start;
+    while index < input.len() && (0x30..=0x3f).contains(&input[index]) {
+        index += 1;
+    }
+    let params_end = index;
+    while index < input.len() && (0x20..=0x2f).contains(&input[index]) {
+        index += 1;
+    }
+    let intermediates_end = index;
+    match input.get(index) {
+        None => Recognized::Incomplete,
+        Some(final_byte @ 0x40..=0x7e) => Recognized::Complete {
+            sequence: EscapeSequence::Csi {
+                params: &input[params_start..params_end],
+                intermediates: &input[params_end..intermediates_end],
+                final_byte: *final_byte,
+            },
+            len: index + 1,
+        },
+        Some(_) => Recognized::Invalid { len: index },
+    }
+}
+
+fn recognize_other(input: &[u8]) -> Recognized<'_> {
+    let mut index = 1;
+    while index < input.len() && (0x20..=0x2f).contains(&input[index]) {
+        index += 1;
+    }
+    match input.get(index) {
+        None => Recognized::Incomplete,
+        Some(0x30..=0x7e) => Recognized::Complete {
+            sequence: EscapeSequence::Other {
+                bytes: &input[1..=index],
+            },
+            len: index + 1,
+        },
+        Some(_) => Recognized::Invalid { len: index },
+    }
+}
+
+/// Returns `(payload_end, sequence_len)` for the OSC, DCS, SOS, PM, or APC string that
+/// starts at the beginning of `input`. The string is terminated by `ESC \` (or `BEL`, for
+/// an OSC). Like the [vt100.net parser](https://vt100.net/emu/dec_ansi_parser), any other
+/// `ESC` ends the string as well, and starts the next escape sequence, which isn't part
+/// of `sequence_len`. The only exception is `ESC ESC` in a DCS, which tmux uses to escape
+/// an `ESC` in a passthrough sequence (eg: `ESC P tmux; ESC ESC ] ...`).
+fn find_string_terminator(input: &[u8]) -> Option<(usize, usize)> {
+    let introducer = input[1];
+    let mut index = 2;
+    while index < input.len() {
+        match input[index] {
+            BEL if introducer == b']' => return Some((index, index + 1)),
+            ESC => match input.get(index + 1) {
+                Some(b'\\') => return Some((index, index + 2)),
+                Some(&ESC) if introducer == b'P' => index += 1,
+                Some(_) => return Some((index, index)),
+                None => return None,
+            },
+            _ => {}
+        }
+        index += 1;
+    }
+    None
+}
+
+/// A piece of a tokenized string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AnsiToken<'a> {
+    /// A run of text w/out any escape sequences in it.
+    Text(&'a str),
+    /// The [SgrCode]s from a single SGR escape sequence. `ESC [ 1 ; 38 ; 5 ; 150 m`
+    /// yields 2 codes.
+    Sgr(Vec<SgrCode>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    /// Byte offset into the input where the offending escape sequence starts.
+    pub offset: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// The input (bytes) is not valid UTF-8.
+    InvalidUtf8,
+    /// The input ends in the middle of an escape sequence.
+    IncompleteSequence,
+    /// The bytes following `ESC` are not a valid escape sequence.
+    InvalidSequence,
+    /// A valid escape sequence that isn't SGR (eg: cursor movement, OSC, etc).
+    UnsupportedSequence,
+    /// A SGR sequence w/ a parameter that doesn't map to a [SgrCode].
+    UnsupportedSgrParameter(String),
+    /// A SGR sequence w/ a missing or out of range 8-bit or 24-bit color.
+    MalformedSgrColor(String),
+}
+
+mod parse_error_impl {
+    use super::{ParseError, ParseErrorKind};
+    use std::fmt::{Display, Formatter, Result};
+
+    impl Display for ParseError {
+        fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+            let offset = self.offset;
+            match &self.kind {
+                ParseErrorKind::InvalidUtf8 => write!(f, "invalid UTF-8 at byte {offset}"),
+                ParseErrorKind::IncompleteSequence => {
+                    write!(f, "incomplete escape sequence at byte {offset}")
+                }
+                ParseErrorKind::InvalidSequence => {
+                    write!(f, "invalid escape sequence at byte {offset}")
+                }
+                ParseErrorKind::UnsupportedSequence => {
+                    write!(f, "unsupported (non SGR) escape sequence at byte {offset}")
+                }
+                ParseErrorKind::UnsupportedSgrParameter(param) => {
+                    write!(f, "unsupported SGR parameter `{param}` at byte {offset}")
+                }
+                ParseErrorKind::MalformedSgrColor(params) => {
+                    write!(f, "malformed SGR color `{params}` at byte {offset}")
+                }
+            }
+        }
+    }
+
+    impl std::error::Error for ParseError {}
+}
+
+/// Splits `input` into [AnsiToken]s. Any escape sequence that isn't a SGR sequence made
+/// up of parameters that map to [SgrCode]s is reported as a [ParseError].
+pub fn tokenize(input: &str) -> Result<Vec<AnsiToken<'_>>, ParseError> {
+    let bytes = input.as_bytes();
+    let mut acc = vec![];
+    let mut text_start = 0;
+    let mut index = 0;
+
+    while index < bytes.len() {
+        if bytes[index] != ESC {
+            index += 1;
+            continue;
+        }
+
+        if text_start < index {
+            acc.push(AnsiToken::Text(&input[text_start..index]));
+        }
+
+        let error = |kind| ParseError {
+            kind,
+            offset: index,
+        };
+        let len = match recognize_escape(&bytes[index..]) {
+            Recognized::Complete {
+                sequence:
+                    EscapeSequence::Csi {
+                        params,
+                        intermediates: [],
+                        final_byte: b'm',
+                    },
+                len,
+            } => {
+                // Params are always ASCII (0x30..=0x3f).
+                let params = std::str::from_utf8(params).unwrap_or_default();
+                let sgr_codes = parse_sgr_params(params).map_err(error)?;
+                acc.push(AnsiToken::Sgr(sgr_codes));
+                len
+            }
+            Recognized::Complete { .. } => {
+                return Err(error(ParseErrorKind::UnsupportedSequence));
+            }
+            Recognized::Incomplete => return Err(error(ParseErrorKind::IncompleteSequence)),
+            Recognized::Invalid { .. } => return Err(error(ParseErrorKind::InvalidSequence)),
+        };
+
+        index += len;
+        text_start = index;
+    }
+
+    if text_start < bytes.len() {
+        acc.push(AnsiToken::Text(&input[text_start..]));
+    }
+
+    Ok(acc)
+}
+
+/// Same as [tokenize], but for a byte stream (eg: the output of a subprocess).
+pub fn tokenize_bytes(input: &[u8]) -> Result<Vec<AnsiToken<'_>>, ParseError> {
+    match std::str::from_utf8(input) {
+        Ok(input) => tokenize(input),
+        Err(error) => Err(ParseError {
+            kind: ParseErrorKind::InvalidUtf8,
+            offset: error.valid_up_to(),
+        }),
+    }
+}
+
+/// Converts the parameters of a single SGR sequence (eg: `1;38;5;150` from
+/// `ESC [ 1 ; 38 ; 5 ; 150 m`) into [SgrCode]s. Both the `;` and the `:` separated forms
+/// of 8-bit and 24-bit colors are supported (eg: `38;2;1;2;3`, `38:2::1:2:3`).
+pub fn parse_sgr_params(params: &str) -> Result<Vec<SgrCode>, ParseErrorKind> {
+    // `ESC [ m` is the same as `ESC [ 0 m`.
+    if params.is_empty() {
+        return Ok(vec![SgrCode::Reset]);
+    }
+
+    let mut acc = vec![];
+    for param in split_sgr_params(params) {
+        // Colon separated sub-parameters are self contained (eg: `38:5:150`).
+        if param.contains(':') {
+            let mut sub_fields = param.split(':');
+            let code = sub_fields.next().unwrap_or_default();
+            let sub_fields: Vec<&str> = sub_fields.collect();
+            acc.push(parse_extended_color(code, &sub_fields, true, param)?);
+            continue;
+        }
+
+        let mut fields = param.split(';');
+        let code = fields.next().unwrap_or_default();
+        let code = match code {
+            "" => SgrCode::Reset,
+            "38" | "48" => {
+                let mut sub_fields: Vec<&str> = fields.collect();
+                let len = match sub_fields.first() {
+                    Some(&"5") => 2,
+                    Some(&"2") => 4,
+                    _ => 1,
+                };
+                sub_fields.resize(len, "");
+                let context = format!("{code};{}", sub_fields.join(";"));
+                parse_extended_color(code, &sub_fields, false, &context)?
+            }
+            _ => parse_simple_sgr_param(code)?,
+        };
+        acc.push(code);
+    }
+
+    Ok(acc)
+}
+
+/// Splits the parameters of a SGR sequence at the `;`s, except for the ones in the `;`
+/// separated form of a 8-bit or 24-bit color, eg: `1;38;5;150;4:3` yields `1`,
+/// `38;5;150` and `4:3`.
+pub(crate) fn split_sgr_params(params: &str) -> Vec<&str> {
+    let fields: Vec<&str> = params.split(';').collect();
+    let mut acc = vec![];
+    let mut start = 0;
+    let mut index = 0;
+
+    while index < fields.len() {
+        let count = match fields[index] {
+            "38" | "48" => match fields.get(index + 1) {
+                Some(&"5") => 3,
+                Some(&"2") => 5,
+                _ => 2,
+            },
+            _ => 1,
+        };
+        let count = count.min(fields.len() - index);
+        // The fields, and the `;`s in between them.
+        let len = fields[index..index + count]
+            .iter()
+            .map(|it| it.len() + 1)
+            .sum::<usize>()
+            - 1;
+        acc.push(&params[start..start + len]);
+        start += len + 1;
+        index += count;
+    }
+
+    acc
+}
+
+#[rustfmt::skip]
+fn parse_simple_sgr_param(field: &str) -> Result<SgrCode, ParseErrorKind> {
+    let unsupported = || ParseErrorKind::UnsupportedSgrParameter(field.to_string());
+    let value: u8 = field.parse().map_err(|_| unsupported())?;
+    let code = match value {
+        0         => SgrCode::Reset,
+        1         => SgrCode::Bold,
+        2         => SgrCode::Dim,
+        3         => SgrCode::Italic,
+        4         => SgrCode::Underline,
+        5         => SgrCode::SlowBlink,
+        6         => SgrCode::RapidBlink,
+        7         => SgrCode::Invert,
+        8         => SgrCode::Hidden,
+        9         => SgrCode::Strikethrough,
+        22        => SgrCode::ResetBoldDim,
+        23        => SgrCode::ResetItalic,
+        24        => SgrCode::ResetUnderline,
+        25        => SgrCode::ResetBlink,
+        27        => SgrCode::ResetInvert,
+        28        => SgrCode::ResetHidden,
+        29        => SgrCode::ResetStrikethrough,
+        30..=37   => SgrCode::ForegroundAnsi16(value - 30),
+        39        => SgrCode::ResetForeground,
+        40..=47   => SgrCode::BackgroundAnsi16(value - 40),
+        49        => SgrCode::ResetBackground,
+        53        => SgrCode::Overline,
+        55        => SgrCode::ResetOverline,
+        90..=97   => SgrCode::ForegroundAnsi16(value - 90 + 8),
+        100..=107 => SgrCode::BackgroundAnsi16(value - 100 + 8),
+        _         => return Err(unsupported()),
+    };
+    Ok(code)
+}
+
+/// Parses `38` / `48` followed by `5;<index>` or `2;<r>;<g>;<b>`. In the colon separated
+/// form, `2` may be followed by an (ignored) color space id, eg: `38:2::r:g:b`. The
+/// `context` is the whole color, as it appears in the input, and is used in errors.
+fn parse_extended_color(
+    code: &str,
+    sub_fields: &[&str],
+    is_colon_separated: bool,
+    context: &str,
+) -> Result<SgrCode, ParseErrorKind> {
+    let malformed = || ParseErrorKind::MalformedSgrColor(context.to_string());
+    let is_foreground = match code {
+        "38" => true,
+        "48" => false,
+        _ => return Err(ParseErrorKind::UnsupportedSgrParameter(context.to_string())),
+    };
+    let parse_u8 = |it: &str| it.parse::<u8>().map_err(|_| malformed());
+
+    match sub_fields {
+        ["5", index] => {
+            let index = parse_u8(index)?;
+            Ok(if is_foreground {
+                SgrCode::ForegroundAnsi256(index)
+            } else {
+                SgrCode::BackgroundAnsi256(index)
+            })
+        }
+        ["2", rgb @ ..] => {
+            let rgb = match rgb {
+                [_color_space_id, r, g, b] if is_colon_separated => [r, g, b],
+                [r, g, b] => [r, g, b],
+                _ => return Err(malformed()),
+            };
+            let (r, g, b) = (parse_u8(rgb[0])?, parse_u8(rgb[1])?, parse_u8(rgb[2])?);
+            Ok(if is_foreground {
+                SgrCode::ForegroundRGB(r, g, b)
+            } else {
+                SgrCode::BackgroundRGB(r, g, b)
+            })
+        }
+        _ => Err(malformed()),
+    }
+}
+
+/// Applies a single [SgrCode] to the `style` that is currently in effect. Colors and
+/// attributes that are set more than once are only kept once.
+pub fn apply_sgr_code(style: &mut Vec<Style>, sgr_code: SgrCode) {
+    let mut set = |item: Style| {
+        style.retain(|it| std::mem::discriminant(it) != std::mem::discriminant(&item));
+        style.push(item);
+    };
+
+    match sgr_code {
+        SgrCode::Reset => style.clear(),
+        SgrCode::Bold => set(Style::Bold),
+        SgrCode::Dim => set(Style::Dim),
+        SgrCode::Italic => set(Style::Italic),
+        SgrCode::Underline => set(Style::Underline),
+        SgrCode::Overline => set(Style::Overline),
+        SgrCode::SlowBlink => set(Style::SlowBlink),
+        SgrCode::RapidBlink => set(Style::RapidBlink),
+        SgrCode::Invert => set(Style::Invert),
+        SgrCode::Hidden => set(Style::Hidden),
+        SgrCode::Strikethrough => set(Style::Strikethrough),
+        SgrCode::ForegroundAnsi16(index) => set(Style::Foreground(Color::Ansi16(index))),
+        SgrCode::BackgroundAnsi16(index) => set(Style::Background(Color::Ansi16(index))),
+        SgrCode::ForegroundAnsi256(index) => set(Style::Foreground(Color::Ansi256(index))),
+        SgrCode::BackgroundAnsi256(index) => set(Style::Background(Color::Ansi256(index))),
+        SgrCode::ForegroundRGB(r, g, b) => set(Style::Foreground(Color::Rgb(r, g, b))),
+        SgrCode::BackgroundRGB(r, g, b) => set(Style::Background(Color::Rgb(r, g, b))),
+        SgrCode::ResetBoldDim => style.retain(|it| !matches!(it, Style::Bold | Style::Dim)),
+        SgrCode::ResetItalic => style.retain(|it| *it != Style::Italic),
+        SgrCode::ResetUnderline => style.retain(|it| *it != Style::Underline),
+        SgrCode::ResetBlink => {
+            style.retain(|it| !matches!(it, Style::SlowBlink | Style::RapidBlink))
+        }
+        SgrCode::ResetInvert => style.retain(|it| *it != Style::Invert),
+        SgrCode::ResetHidden => style.retain(|it| *it != Style::Hidden),
+        SgrCode::ResetStrikethrough => style.retain(|it| *it != Style::Strikethrough),
+        SgrCode::ResetOverline => style.retain(|it| *it != Style::Overline),
+        SgrCode::ResetForeground => style.retain(|it| !matches!(it, Style::Foreground(_))),
+        SgrCode::ResetBackground => style.retain(|it| !matches!(it, Style::Background(_))),
+    }
+}
+
+/// A run of text, and the [Style]s that are in effect for it. Returned by
+/// [parse_styled_spans].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct StyledSpan {
+    pub text: String,
+    pub style: Vec<Style>,
+}
+
+/// Parses `input` into a list of [StyledSpan]s, where each span holds the [Style]s that
+/// are in effect for its text. Adjacent runs of text w/ the same styles are merged into
+/// a single span.
+pub fn parse_styled_spans(input: &str) -> Result<Vec<StyledSpan>, ParseError> {
+    let mut acc: Vec<StyledSpan> = vec![];
+    let mut current_style = vec![];
+
+    for token in tokenize(input)? {
+        match token {
+            AnsiToken::Sgr(sgr_codes) => {
+                for sgr_code in sgr_codes {
+                    apply_sgr_code(&mut current_style, sgr_code);
+                }
+            }
+            AnsiToken::Text(text) => match acc.last_mut() {
+                Some(last) if last.style == current_style => last.text.push_str(text),
+                _ => acc.push(StyledSpan {
+                    text: text.to_string(),
+                    style: current_style.clone(),
+                }),
+            },
+        }
+    }
+
+    Ok(acc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AnsiStyledText, ColorSupport, NoColorStyle, OwnedStyledText};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_recognize_escape() {
+        assert_eq!(
+            recognize_escape(b"\x1b[1;2mabc"),
+            Recognized::Complete {
+                sequence: EscapeSequence::Csi {
+                    params: b"1;2",
+                    intermediates: b"",
+                    final_byte: b'm',
+                },
+                len: 6,
+            }
+        );
+        assert_eq!(
+            recognize_escape(b"\x1b]11;?\x07"),
+            Recognized::Complete {
+                sequence: EscapeSequence::Osc { payload: b"11;?" },
+                len: 7,
+            }
+        );
+        assert_eq!(
+            recognize_escape(b"\x1bP+q5463\x1b\\"),
+            Recognized::Complete {
+                sequence: EscapeSequence::Dcs {
+                    kind: b'P',
+                    payload: b"+q5463",
+                },
+                len: 10,
+            }
+        );
+        assert_eq!(
+            recognize_escape(b"\x1b(B"),
+            Recognized::Complete {
+                sequence: EscapeSequence::Other { bytes: b"(B" },
+                len: 3,
+            }
+        );
+        assert_eq!(recognize_escape(b"\x1b"), Recognized::Incomplete);
+        assert_eq!(recognize_escape(b"\x1b[38;5"), Recognized::Incomplete);
+        assert_eq!(recognize_escape(b"\x1b]11;rgb:0"), Recognized::Incomplete);
+        assert_eq!(
+            recognize_escape(b"\x1b]11;rgb:0\x1b"),
+            Recognized::Incomplete
+        );
+        assert_eq!(
+            recognize_escape(b"\x1b]8;;\x1b[1m"),
+            Recognized::Complete {
+                sequence: EscapeSequence::Osc { payload: b"8;;" },
+                len: 5,
+            }
+        );
+        assert_eq!(
+            recognize_escape(b"\x1b[1\x07"),
+            Recognized::Invalid { len: 3 }
+        );
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("a\x1b[1;38;5;150mb\x1b[48;2;1;2;3mc\x1b[0m").unwrap(),
+            vec![
+                AnsiToken::Text("a"),
+                AnsiToken::Sgr(vec![SgrCode::Bold, SgrCode::ForegroundAnsi256(150)]),
+                AnsiToken::Text("b"),
+                AnsiToken::Sgr(vec![SgrCode::BackgroundRGB(1, 2, 3)]),
+                AnsiToken::Text("c"),
+                AnsiToken::Sgr(vec![SgrCode::Reset]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_sgr_params() {
+        assert_eq!(parse_sgr_params("").unwrap(), vec![SgrCode::Reset]);
+        assert_eq!(
+            parse_sgr_params("31;102;22;39").unwrap(),
+            vec![
+                SgrCode::ForegroundAnsi16(1),
+                SgrCode::BackgroundAnsi16(10),
+                SgrCode::ResetBoldDim,
+                SgrCode::ResetForeground,
+            ]
+        );
+        assert_eq!(
+            parse_sgr_params("38:2::1:2:3;48:5:200;38:2:4:5:6").unwrap(),
+            vec![
+                SgrCode::ForegroundRGB(1, 2, 3),
+                SgrCode::BackgroundAnsi256(200),
+                SgrCode::ForegroundRGB(4, 5, 6),
+            ]
+        );
+        assert_eq!(
+            parse_sgr_params("21"),
+            Err(ParseErrorKind::UnsupportedSgrParameter("21".to_string()))
+        );
+        assert_eq!(
+            parse_sgr_params("38;5"),
+            Err(ParseErrorKind::MalformedSgrColor("38;5;".to_string()))
+        );
+        assert_eq!(
+            parse_sgr_params("38;5;300"),
+            Err(ParseErrorKind::MalformedSgrColor("38;5;300".to_string()))
+        );
+        assert_eq!(
+            parse_sgr_params("38:5:300"),
+            Err(ParseErrorKind::MalformedSgrColor("38:5:300".to_string()))
+        );
+        assert_eq!(
+            parse_sgr_params("1;48:2:1:2"),
+            Err(ParseErrorKind::MalformedSgrColor("48:2:1:2".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_tokenize_errors() {
+        assert_eq!(
+            tokenize("ab\x1b[2K").unwrap_err(),
+            ParseError {
+                kind: ParseErrorKind::UnsupportedSequence,
+                offset: 2,
+            }
+        );
+        assert_eq!(
+            tokenize("ab\x1b[1").unwrap_err(),
+            ParseError {
+                kind: ParseErrorKind::IncompleteSequence,
+                offset: 2,
+            }
+        );
+        assert_eq!(
+            tokenize("\x1b[1;21m").unwrap_err(),
+            ParseError {
+                kind: ParseErrorKind::UnsupportedSgrParameter("21".to_string()),
+                offset: 0,
+            }
+        );
+        assert_eq!(
+            tokenize_bytes(b"ok\xff").unwrap_err(),
+            ParseError {
+                kind: ParseErrorKind::InvalidUtf8,
+                offset: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_styled_spans() {
+        let spans =
+            parse_styled_spans("plain \x1b[1;31mbold red\x1b[22m red\x1b[0m plain").unwrap();
+        assert_eq!(
+            spans,
+            vec![
+                StyledSpan {
+                    text: "plain ".to_string(),
+                    style: vec![],
+                },
+                StyledSpan {
+                    text: "bold red".to_string(),
+                    style: vec![Style::Bold, Style::Foreground(Color::Ansi16(1))],
+                },
+                StyledSpan {
+                    text: " red".to_string(),
+                    style: vec![Style::Foreground(Color::Ansi16(1))],
+                },
+                StyledSpan {
+                    text: " plain".to_string(),
+                    style: vec![],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_styled_spans_round_trip() {
+        let style = [
+            Style::Bold,
+            Style::Italic,
+            Style::Foreground(Color::Rgb(1, 2, 3)),
+            Style::Background(Color::Ansi256(150)),
+        ];
+        let text = AnsiStyledText {
+            text: "Hello",
+            style: &style,
+        };
+
+        let rendered = text.to_string_with(ColorSupport::Truecolor, NoColorStyle::PlainText);
+        assert_eq!(
+            parse_styled_spans(&rendered).unwrap(),
+            vec![StyledSpan {
+                text: "Hello".to_string(),
+                style: vec![
+                    Style::Bold,
+                    Style::Italic,
+                    Style::Foreground(Color::Rgb(1, 2, 3)),
+                    Style::Background(Color::Rgb(175, 215, 135)),
+                ],
+            }]
+        );
+
+        // The spans can be rendered again.
+        let spans = parse_styled_spans(&rendered).unwrap();
+        let owned = OwnedStyledText::from(spans[0].clone());
+        assert_eq!(
+            owned.to_string_with(ColorSupport::Truecolor, NoColorStyle::PlainText),
+            rendered
+        );
+
+        let rendered = text.to_string_with(ColorSupport::Ansi256, NoColorStyle::PlainText);
+        assert_eq!(
+            parse_styled_spans(&rendered).unwrap(),
+            vec![StyledSpan {
+                text: "Hello".to_string(),
+                style: vec![
+                    Style::Bold,
+                    Style::Italic,
+                    Style::Foreground(Color::Ansi256(16)),
+                    Style::Background(Color::Ansi256(150)),
+                ],
+            }]
+        );
+    }
+}
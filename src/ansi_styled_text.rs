@@ -45,7 +45,11 @@ pub struct AnsiStyledText<'a> {
 }
 
 mod ansi_styled_text_impl {
-    use crate::AnsiStyledText;
+    use crate::{
+        query_runtime_color_support, query_runtime_color_support_in, AnsiStyledText, ColorSupport,
+        Environment, NoColorStyle, RealEnvironment, Stream,
+    };
+    use std::io::{Result, Write};
 
     impl AnsiStyledText<'_> {
         pub fn println(&self) {
@@ -55,9 +59,338 @@ mod ansi_styled_text_impl {
         pub fn print(&self) {
             print!("{}", self);
         }
+
+        /// Same as [AnsiStyledText::println], but prints to stderr, using the color
+        /// support that is detected for stderr (not stdout).
+        pub fn eprintln(&self) {
+            eprintln!("{}", self.eprint_string_in(&RealEnvironment));
+        }
+
+        /// Same as [AnsiStyledText::print], but prints to stderr, using the color
+        /// support that is detected for stderr (not stdout).
+        pub fn eprint(&self) {
+            eprint!("{}", self.eprint_string_in(&RealEnvironment));
+        }
+
+        /// What [AnsiStyledText::eprint] and [AnsiStyledText::eprintln] print.
+        pub(crate) fn eprint_string_in(&self, env: &impl Environment) -> String {
+            self.to_string_for_in(env, Stream::Stderr)
+        }
+
+        /// Writes the formatted string to `writer` (eg: a file, a socket, a `Vec<u8>`)
+        /// using the given `color_support`. Use [query_runtime_color_support] to get the
+        /// color support for stdout or stderr.
+        pub fn write_to(&self, writer: &mut impl Write, color_support: ColorSupport) -> Result<()> {
+            let string = self.to_string_with(color_support, NoColorStyle::default());
+            writer.write_all(string.as_bytes())
+        }
+
+        /// Returns the formatted string using the color support for `stream`.
+        pub fn to_string_for(&self, stream: Stream) -> String {
+            self.to_string_with(query_runtime_color_support(stream), NoColorStyle::default())
+        }
+
+        /// Same as [AnsiStyledText::to_string_for], but the color support is detected in
+        /// `env`, see [query_runtime_color_support_in].
+        pub fn to_string_for_in(&self, env: &impl Environment, stream: Stream) -> String {
+            self.to_string_with(
+                query_runtime_color_support_in(env, stream),
+                NoColorStyle::default(),
+            )
+        }
+
+        /// Returns the formatted string using the given `color_support`, instead of the
+        /// one that is detected at runtime. When `color_support` is
+        /// [ColorSupport::NoColor], `no_color_style` decides whether text attributes
+        /// (bold, italic, etc) are still emitted.
+        pub fn to_string_with(
+            &self,
+            color_support: ColorSupport,
+            no_color_style: NoColorStyle,
+        ) -> String {
+            let mut acc = String::new();
+            // Writing to a String can't fail.
+            let _ = self.write_with(color_support, no_color_style, &mut acc);
+            acc
+        }
+    }
+}
+
+/// Owned counterpart of [AnsiStyledText], which is handy when the text and styles have
+/// to be produced at runtime (eg: from the [StyledSpan]s that [parse_styled_spans]
+/// returns for the output of another process), returned from functions, or stored in
+/// structs.
+///
+/// ## Example usage:
+///
+/// ```rust
+/// use r3bl_ansi_color::*;
+///
+/// let text: OwnedStyledText = "Hello"
+///     .styled()
+///     .bold()
+///     .fg(Color::Rgb(50, 50, 50))
+///     .bg(Color::Ansi256(150));
+/// text.println();
+///
+/// // Convert to and from the borrowed counterpart.
+/// let borrowed: AnsiStyledText<'_> = (&text).into();
+/// assert_eq!(OwnedStyledText::from(borrowed), text);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct OwnedStyledText {
+    pub text: String,
+    pub style: Vec<Style>,
+}
+
+/// Extension trait to create an [OwnedStyledText] from a `&str` or `String`, so that the
+/// styles can be added to it fluently, eg: `"hi".styled().bold()`.
+pub trait StyledTextExt {
+    fn styled(self) -> OwnedStyledText;
+}
+
+mod owned_styled_text_impl {
+    use crate::{
+        AnsiStyledText, Color, ColorSupport, NoColorStyle, OwnedStyledText, Style, StyledSpan,
+        StyledTextExt,
+    };
+    use std::fmt::{Display, Formatter, Result};
+
+    impl StyledTextExt for &str {
+        fn styled(self) -> OwnedStyledText {
+            self.to_string().styled()
+        }
+    }
+
+    impl StyledTextExt for String {
+        fn styled(self) -> OwnedStyledText {
+            OwnedStyledText {
+                text: self,
+                style: vec![],
+            }
+        }
+    }
+
+    impl OwnedStyledText {
+        /// Borrows `self` as an [AnsiStyledText] (which is used to render it).
+        pub fn as_ansi_styled_text(&self) -> AnsiStyledText<'_> {
+            AnsiStyledText {
+                text: &self.text,
+                style: &self.style,
+            }
+        }
+
+        /// Adds `style` to `self`. If `style` is already set (eg: a foreground color),
+        /// then it is replaced.
+        pub fn with_style(mut self, style: Style) -> Self {
+            self.style
+                .retain(|it| std::mem::discriminant(it) != std::mem::discriminant(&style));
+            self.style.push(style);
+            self
+        }
+
+        /// Accepts anything that converts into a [Color], eg: [RgbColor](crate::RgbColor),
+        /// [HslColor](crate::HslColor), [OklchColor](crate::OklchColor).
+        pub fn fg(self, color: impl Into<Color>) -> Self {
+            self.with_style(Style::Foreground(color.into()))
+        }
+
+        pub fn bg(self, color: impl Into<Color>) -> Self {
+            self.with_style(Style::Background(color.into()))
+        }
+
+        pub fn bold(self) -> Self {
+            self.with_style(Style::Bold)
+        }
+
+        pub fn dim(self) -> Self {
+            self.with_style(Style::Dim)
+        }
+
+        pub fn italic(self) -> Self {
+            self.with_style(Style::Italic)
+        }
+
+        pub fn underline(self) -> Self {
+            self.with_style(Style::Underline)
+        }
+
+        pub fn overline(self) -> Self {
+            self.with_style(Style::Overline)
+        }
+
+        pub fn slow_blink(self) -> Self {
+            self.with_style(Style::SlowBlink)
+        }
+
+        pub fn rapid_blink(self) -> Self {
+            self.with_style(Style::RapidBlink)
+        }
+
+        pub fn invert(self) -> Self {
+            self.with_style(Style::Invert)
+        }
+
+        pub fn hidden(self) -> Self {
+            self.with_style(Style::Hidden)
+        }
+
+        pub fn strikethrough(self) -> Self {
+            self.with_style(Style::Strikethrough)
+        }
+
+        pub fn println(&self) {
+            self.as_ansi_styled_text().println();
+        }
+
+        pub fn print(&self) {
+            self.as_ansi_styled_text().print();
+        }
+
+        pub fn eprintln(&self) {
+            self.as_ansi_styled_text().eprintln();
+        }
+
+        pub fn eprint(&self) {
+            self.as_ansi_styled_text().eprint();
+        }
+
+        /// Same as [AnsiStyledText::write_to].
+        pub fn write_to(
+            &self,
+            writer: &mut impl std::io::Write,
+            color_support: ColorSupport,
+        ) -> std::io::Result<()> {
+            self.as_ansi_styled_text().write_to(writer, color_support)
+        }
+
+        /// Same as [AnsiStyledText::to_string_with].
+        pub fn to_string_with(
+            &self,
+            color_support: ColorSupport,
+            no_color_style: NoColorStyle,
+        ) -> String {
+            self.as_ansi_styled_text()
+                .to_string_with(color_support, no_color_style)
+        }
+    }
+
+    impl From<AnsiStyledText<'_>> for OwnedStyledText {
+        fn from(value: AnsiStyledText<'_>) -> Self {
+            OwnedStyledText {
+                text: value.text.to_string(),
+                style: value.style.to_vec(),
+            }
+        }
+    }
+
+    impl From<StyledSpan> for OwnedStyledText {
+        fn from(value: StyledSpan) -> Self {
+            OwnedStyledText {
+                text: value.text,
+                style: value.style,
+            }
+        }
+    }
+
+    impl<'a> From<&'a OwnedStyledText> for AnsiStyledText<'a> {
+        fn from(value: &'a OwnedStyledText) -> Self {
+            value.as_ansi_styled_text()
+        }
+    }
+
+    impl Display for OwnedStyledText {
+        fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+            write!(f, "{}", self.as_ansi_styled_text())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use crate::*;
+        use pretty_assertions::assert_eq;
+
+        fn make_owned_styled_text() -> OwnedStyledText {
+            let text = String::from("Hello");
+            text.styled().bold().fg(Color::Rgb(1, 2, 3))
+        }
+
+        #[test]
+        fn test_builder() {
+            let text = "Hello"
+                .styled()
+                .bold()
+                .fg(Color::Rgb(1, 2, 3))
+                .bg(Color::Ansi256(150))
+                .fg(Color::Ansi256(16))
+                .bold();
+
+            assert_eq!(
+                text,
+                OwnedStyledText {
+                    text: "Hello".to_string(),
+                    style: vec![
+                        Style::Background(Color::Ansi256(150)),
+                        Style::Foreground(Color::Ansi256(16)),
+                        Style::Bold,
+                    ],
+                }
+            );
+
+            assert_eq!(
+                text.to_string_with(ColorSupport::Ansi256, NoColorStyle::PlainText),
+                "\x1b[48;5;150m\x1b[38;5;16m\x1b[1mHello\x1b[0m"
+            );
+        }
+
+        #[test]
+        fn test_convert_between_owned_and_borrowed() {
+            let owned = make_owned_styled_text();
+            let borrowed = AnsiStyledText::from(&owned);
+            assert_eq!(
+                borrowed,
+                AnsiStyledText {
+                    text: "Hello",
+                    style: &[Style::Bold, Style::Foreground(Color::Rgb(1, 2, 3))],
+                }
+            );
+            assert_eq!(OwnedStyledText::from(borrowed), owned);
+        }
     }
 }
 
+/// Controls what is emitted for an [AnsiStyledText] when [ColorSupport::NoColor] is in
+/// effect (eg: `NO_COLOR` is set, or the output is piped).
+///
+/// This can only be picked w/ `to_string_with` (eg: [AnsiStyledText::to_string_with]).
+/// Everything else (`Display`, `print`, `println`, `write_to`, etc) always uses
+/// [NoColorStyle::PlainText]. To keep the attributes in that case, use
+/// [query_runtime_color_support] to get the color support, and pass it to
+/// `to_string_with`:
+///
+/// ```rust
+/// use r3bl_ansi_color::*;
+///
+/// let text = AnsiStyledText {
+///     text: "Hello",
+///     style: &[Style::Bold, Style::Foreground(Color::Ansi256(150))],
+/// };
+/// let color_support = query_runtime_color_support(Stream::Stdout);
+/// println!("{}", text.to_string_with(color_support, NoColorStyle::KeepAttributes));
+/// assert_eq!(
+///     text.to_string_with(ColorSupport::NoColor, NoColorStyle::KeepAttributes),
+///     "\x1b[1mHello\x1b[0m"
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NoColorStyle {
+    /// Only emit the text, w/out any SGR codes.
+    #[default]
+    PlainText,
+    /// Drop the colors, but keep the text attributes (bold, italic, underline, etc).
+    KeepAttributes,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Style {
     Foreground(Color),
@@ -76,10 +409,10 @@ pub enum Style {
 
 mod style_impl {
     use crate::{
-        color_support_override_get, supports_color, Color, ColorSupport, ColorSupportOverride,
-        RgbColor, SgrCode, Stream, Style, TransformColor,
+        query_runtime_color_support, Color, ColorSupport, NoColorStyle, RgbColor, SgrCode, Stream,
+        Style, TransformColor,
     };
-    use std::fmt::{Display, Formatter, Result};
+    use std::fmt::{Display, Formatter, Result, Write};
 
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     enum ColorKind {
@@ -87,79 +420,161 @@ mod style_impl {
         Background,
     }
 
-    fn query_runtime_color_support() -> ColorSupport {
-        match color_support_override_get() {
-            ColorSupportOverride::NotSet => supports_color(Stream::Stdout),
-            ColorSupportOverride::Ansi256 => ColorSupport::Ansi256,
-            ColorSupportOverride::Truecolor => ColorSupport::Truecolor,
-        }
-    }
-
-    fn fmt_color(color: Color, color_kind: ColorKind, f: &mut Formatter<'_>) -> Result {
-        match query_runtime_color_support() {
+    fn color_to_sgr_code(
+        color: Color,
+        color_kind: ColorKind,
+        color_support: ColorSupport,
+    ) -> Option<SgrCode> {
+        match color_support {
+            ColorSupport::Ansi16 => {
+                // ANSI 16 color mode.
+                let index = color.as_ansi16().index();
+                Some(match color_kind {
+                    ColorKind::Foreground => SgrCode::ForegroundAnsi16(index),
+                    ColorKind::Background => SgrCode::BackgroundAnsi16(index),
+                })
+            }
             ColorSupport::Ansi256 => {
                 // ANSI 256 color mode.
                 let color = color.as_ansi256();
                 let index = color.index;
-                write!(
-                    f,
-                    "{}",
-                    match color_kind {
-                        ColorKind::Foreground => SgrCode::ForegroundAnsi256(index),
-                        ColorKind::Background => SgrCode::BackgroundAnsi256(index),
-                    }
-                )
+                Some(match color_kind {
+                    ColorKind::Foreground => SgrCode::ForegroundAnsi256(index),
+                    ColorKind::Background => SgrCode::BackgroundAnsi256(index),
+                })
             }
-            _ => {
+            ColorSupport::Truecolor => {
                 // True color mode.
                 let color = color.as_rgb();
                 let RgbColor { red, green, blue } = color;
-                write!(
-                    f,
-                    "{}",
-                    match color_kind {
-                        ColorKind::Foreground => SgrCode::ForegroundRGB(red, green, blue),
-                        ColorKind::Background => SgrCode::BackgroundRGB(red, green, blue),
-                    }
-                )
+                Some(match color_kind {
+                    ColorKind::Foreground => SgrCode::ForegroundRGB(red, green, blue),
+                    ColorKind::Background => SgrCode::BackgroundRGB(red, green, blue),
+                })
             }
+            // No color mode, so nothing to emit.
+            ColorSupport::NoColor => None,
         }
     }
 
-    impl Display for Style {
-        fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+    impl Style {
+        /// Returns `true` if this is a color (foreground or background), and `false` if
+        /// it is a text attribute (bold, italic, etc).
+        pub fn is_color(&self) -> bool {
+            matches!(self, Style::Foreground(_) | Style::Background(_))
+        }
+
+        /// Returns `true` if this style emits any SGR code for the given `color_support`
+        /// and `no_color_style`.
+        pub(crate) fn is_emitted(
+            &self,
+            color_support: ColorSupport,
+            no_color_style: NoColorStyle,
+        ) -> bool {
+            match color_support {
+                ColorSupport::NoColor => {
+                    no_color_style == NoColorStyle::KeepAttributes && !self.is_color()
+                }
+                _ => true,
+            }
+        }
+
+        /// Returns the [SgrCode] that is emitted for this style, for the given
+        /// `color_support` and `no_color_style`. Colors are converted to fit
+        /// `color_support`. Returns `None` if nothing is emitted.
+        pub fn as_sgr_code(
+            &self,
+            color_support: ColorSupport,
+            no_color_style: NoColorStyle,
+        ) -> Option<SgrCode> {
+            if !self.is_emitted(color_support, no_color_style) {
+                return None;
+            }
             match self {
-                Style::Foreground(color) => fmt_color(*color, ColorKind::Foreground, f),
-                Style::Background(color) => fmt_color(*color, ColorKind::Background, f),
-                Style::Bold => write!(f, "{}", SgrCode::Bold),
-                Style::Dim => write!(f, "{}", SgrCode::Dim),
-                Style::Italic => write!(f, "{}", SgrCode::Italic),
-                Style::Underline => write!(f, "{}", SgrCode::Underline),
-                Style::SlowBlink => write!(f, "{}", SgrCode::SlowBlink),
-                Style::RapidBlink => write!(f, "{}", SgrCode::RapidBlink),
-                Style::Invert => write!(f, "{}", SgrCode::Invert),
-                Style::Hidden => write!(f, "{}", SgrCode::Hidden),
-                Style::Strikethrough => write!(f, "{}", SgrCode::Strikethrough),
-                Style::Overline => write!(f, "{}", SgrCode::Overline),
+                Style::Foreground(color) => {
+                    color_to_sgr_code(*color, ColorKind::Foreground, color_support)
+                }
+                Style::Background(color) => {
+                    color_to_sgr_code(*color, ColorKind::Background, color_support)
+                }
+                Style::Bold => Some(SgrCode::Bold),
+                Style::Dim => Some(SgrCode::Dim),
+                Style::Italic => Some(SgrCode::Italic),
+                Style::Underline => Some(SgrCode::Underline),
+                Style::SlowBlink => Some(SgrCode::SlowBlink),
+                Style::RapidBlink => Some(SgrCode::RapidBlink),
+                Style::Invert => Some(SgrCode::Invert),
+                Style::Hidden => Some(SgrCode::Hidden),
+                Style::Strikethrough => Some(SgrCode::Strikethrough),
+                Style::Overline => Some(SgrCode::Overline),
             }
         }
+
+        pub(crate) fn write_with(
+            &self,
+            color_support: ColorSupport,
+            no_color_style: NoColorStyle,
+            f: &mut impl Write,
+        ) -> Result {
+            match self.as_sgr_code(color_support, no_color_style) {
+                Some(sgr_code) => write!(f, "{}", sgr_code),
+                None => Ok(()),
+            }
+        }
+    }
+
+    impl Display for Style {
+        fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+            self.write_with(
+                query_runtime_color_support(Stream::Stdout),
+                NoColorStyle::default(),
+                f,
+            )
+        }
     }
 }
 
 mod display_trait_impl {
-    use crate::{AnsiStyledText, SgrCode};
-    use std::fmt::{Display, Formatter, Result};
+    use crate::{
+        query_runtime_color_support, AnsiStyledText, ColorSupport, NoColorStyle, SgrCode, Stream,
+    };
+    use std::fmt::{Display, Formatter, Result, Write};
+
+    impl AnsiStyledText<'_> {
+        pub(crate) fn write_with(
+            &self,
+            color_support: ColorSupport,
+            no_color_style: NoColorStyle,
+            f: &mut impl Write,
+        ) -> Result {
+            for style_item in self.style {
+                style_item.write_with(color_support, no_color_style, f)?;
+            }
+            f.write_str(self.text)?;
+            // Only reset if something was emitted (or if colors are enabled, which always
+            // ends w/ a reset).
+            let needs_reset = match color_support {
+                ColorSupport::NoColor => self
+                    .style
+                    .iter()
+                    .any(|it| it.is_emitted(color_support, no_color_style)),
+                _ => true,
+            };
+            if needs_reset {
+                write!(f, "{}", SgrCode::Reset)?;
+            }
+            Ok(())
+        }
+    }
 
     // https://doc.rust-lang.org/std/fmt/trait.Display.html
     impl Display for AnsiStyledText<'_> {
         fn fmt(&self, formatter: &mut Formatter<'_>) -> Result {
-            let mut style_string_vec = vec![];
-            for style_item in self.style {
-                style_string_vec.push(style_item.to_string());
-            }
-            style_string_vec.push(self.text.to_string());
-            style_string_vec.push(SgrCode::Reset.to_string());
-            write!(formatter, "{}", style_string_vec.join(""))
+            self.write_with(
+                query_runtime_color_support(Stream::Stdout),
+                NoColorStyle::default(),
+                formatter,
+            )
         }
     }
 
@@ -170,7 +585,7 @@ mod display_trait_impl {
 
         #[test]
         fn test_formatted_string_creation_ansi256() -> Result<(), String> {
-            color_support_override_set(ColorSupportOverride::Ansi256);
+            let _guard = ColorSupportOverride::scoped(ColorSupportOverride::Ansi256);
             let eg_1 = AnsiStyledText {
                 text: "Hello",
                 style: &[
@@ -204,7 +619,7 @@ mod display_trait_impl {
 
         #[test]
         fn test_formatted_string_creation_truecolor() -> Result<(), String> {
-            color_support_override_set(ColorSupportOverride::Truecolor);
+            let _guard = ColorSupportOverride::scoped(ColorSupportOverride::Truecolor);
             let eg_1 = AnsiStyledText {
                 text: "Hello",
                 style: &[
@@ -235,5 +650,140 @@ mod display_trait_impl {
 
             Ok(())
         }
+
+        #[test]
+        fn test_write_to() {
+            let eg = AnsiStyledText {
+                text: "Hello",
+                style: &[Style::Bold, Style::Foreground(Color::Ansi256(150))],
+            };
+
+            let mut acc: Vec<u8> = vec![];
+            eg.write_to(&mut acc, ColorSupport::Ansi256).unwrap();
+            eg.write_to(&mut acc, ColorSupport::NoColor).unwrap();
+            assert_eq!(
+                String::from_utf8(acc).unwrap(),
+                "\x1b[1m\x1b[38;5;150mHello\x1b[0mHello"
+            );
+        }
+
+        #[test]
+        fn test_eprint_detects_on_stderr() {
+            let eg = AnsiStyledText {
+                text: "Hello",
+                style: &[Style::Foreground(Color::Ansi256(150))],
+            };
+            // Stdout is piped, but stderr is a terminal, and vice versa.
+            let stderr_tty = FakeEnvironment::new()
+                .var("TERM", "xterm-256color")
+                .tty(Stream::Stderr);
+            let stdout_tty = FakeEnvironment::new()
+                .var("TERM", "xterm-256color")
+                .tty(Stream::Stdout);
+
+            assert_eq!(
+                eg.eprint_string_in(&stderr_tty),
+                "\x1b[38;5;150mHello\x1b[0m"
+            );
+            assert_eq!(eg.eprint_string_in(&stdout_tty), "Hello");
+            assert_eq!(eg.to_string_for_in(&stderr_tty, Stream::Stdout), "Hello");
+        }
+
+        fn make_eg() -> AnsiStyledText<'static> {
+            AnsiStyledText {
+                text: "Hello",
+                style: &[
+                    Style::Bold,
+                    Style::Underline,
+                    Style::Foreground(Color::Rgb(0, 0, 0)),
+                    Style::Background(Color::Ansi256(150)),
+                ],
+            }
+        }
+
+        /// Resolves the color support the same way as [query_runtime_color_support], w/
+        /// no overrides, but w/ detection running in `env`.
+        fn detect_in(env: &FakeEnvironment) -> ColorSupport {
+            resolve_color_support(
+                ColorSupportOverride::NotSet,
+                ColorSupportOverride::NotSet,
+                || supports_color_in(env, Stream::Stdout),
+            )
+        }
+
+        /// When stdout is piped, [supports_color] returns [ColorSupport::NoColor], so only
+        /// the text should be emitted.
+        #[test]
+        fn test_formatted_string_creation_piped() {
+            let eg = make_eg();
+            let piped = FakeEnvironment::new().var("TERM", "xterm-256color");
+            let color_support = detect_in(&piped);
+            assert_eq!(color_support, ColorSupport::NoColor);
+            assert_eq!(
+                eg.to_string_with(color_support, NoColorStyle::PlainText),
+                "Hello"
+            );
+
+            // The same terminal, when it isn't piped.
+            let color_support = detect_in(&piped.tty(Stream::Stdout));
+            assert_eq!(color_support, ColorSupport::Ansi256);
+            assert_eq!(
+                eg.to_string_with(color_support, NoColorStyle::PlainText),
+                "\x1b[1m\x1b[4m\x1b[38;5;16m\x1b[48;5;150mHello\x1b[0m"
+            );
+        }
+
+        /// When `NO_COLOR` is set, detection returns [ColorSupport::NoColor], even for a
+        /// TTY.
+        #[test]
+        fn test_formatted_string_creation_no_color_var() {
+            let env = FakeEnvironment::new()
+                .var("TERM", "xterm-256color")
+                .var("NO_COLOR", "1")
+                .tty(Stream::Stdout);
+            let color_support = detect_in(&env);
+            assert_eq!(color_support, ColorSupport::NoColor);
+            assert_eq!(
+                make_eg().to_string_with(color_support, NoColorStyle::PlainText),
+                "Hello"
+            );
+        }
+
+        /// When `NO_COLOR` is set, colors are dropped, but attributes can be kept.
+        #[test]
+        fn test_formatted_string_creation_no_color_keep_attributes() {
+            let eg = make_eg();
+            assert_eq!(
+                eg.to_string_with(ColorSupport::NoColor, NoColorStyle::KeepAttributes),
+                "\x1b[1m\x1b[4mHello\x1b[0m"
+            );
+
+            // No attributes to keep, so no trailing reset either.
+            let eg = AnsiStyledText {
+                text: "World",
+                style: &[Style::Foreground(Color::Rgb(1, 2, 3))],
+            };
+            assert_eq!(
+                eg.to_string_with(ColorSupport::NoColor, NoColorStyle::KeepAttributes),
+                "World"
+            );
+        }
+
+        #[test]
+        fn test_formatted_string_creation_explicit_color_support() {
+            let eg = make_eg();
+            assert_eq!(
+                eg.to_string_with(ColorSupport::Ansi256, NoColorStyle::PlainText),
+                "\x1b[1m\x1b[4m\x1b[38;5;16m\x1b[48;5;150mHello\x1b[0m"
+            );
+            assert_eq!(
+                eg.to_string_with(ColorSupport::Truecolor, NoColorStyle::PlainText),
+                "\x1b[1m\x1b[4m\x1b[38;2;0;0;0m\x1b[48;2;175;215;135mHello\x1b[0m"
+            );
+            assert_eq!(
+                eg.to_string_with(ColorSupport::Ansi16, NoColorStyle::PlainText),
+                "\x1b[1m\x1b[4m\x1b[30m\x1b[47mHello\x1b[0m"
+            );
+        }
     }
 }
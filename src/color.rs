@@ -32,17 +32,41 @@ pub trait TransformColor {
     /// Returns the index of a color in 256-color ANSI palette approximating the `self`
     /// color.
     fn as_ansi256(&self) -> Ansi256Color;
+
+    /// Returns the index of a color in the 16 system colors (ANSI basic palette)
+    /// approximating the `self` color. By default, the nearest system color to
+    /// [TransformColor::as_rgb] in the active [Palette](crate::Palette) is used.
+    fn as_ansi16(&self) -> Ansi16Color {
+        crate::convert_rgb_into_ansi16(self.as_rgb())
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Color {
     Rgb(u8, u8, u8),
     Ansi256(u8),
+    /// Index into the 16 system colors (`0..=15`). Larger values are clamped to `15`
+    /// (bright white) when the color is used, see [Ansi16Color::new].
+    Ansi16(u8),
 }
 
 mod color_impl {
     use crate::*;
 
+    impl Color {
+        /// Returns the color that is actually emitted for `color_support`, eg: the
+        /// nearest [Color::Ansi256] in [ColorSupport::Ansi256] mode. In
+        /// [ColorSupport::NoColor] mode, `self` is returned as is.
+        pub fn quantize(&self, color_support: ColorSupport) -> Color {
+            match color_support {
+                ColorSupport::Truecolor => self.as_rgb().into(),
+                ColorSupport::Ansi256 => self.as_ansi256().into(),
+                ColorSupport::Ansi16 => self.as_ansi16().into(),
+                ColorSupport::NoColor => *self,
+            }
+        }
+    }
+
     impl TransformColor for Color {
         fn as_rgb(&self) -> RgbColor {
             match self {
@@ -52,19 +76,52 @@ mod color_impl {
                     blue: *b,
                 },
                 Color::Ansi256(index) => Ansi256Color { index: *index }.as_rgb(),
+                Color::Ansi16(index) => Ansi16Color::new(*index).as_rgb(),
             }
         }
 
         fn as_ansi256(&self) -> Ansi256Color {
             match self {
-                Color::Rgb(red, green, blue) => convert_rgb_into_ansi256(RgbColor {
+                Color::Rgb(red, green, blue) => RgbColor {
                     red: *red,
                     green: *green,
                     blue: *blue,
-                }),
+                }
+                .as_ansi256(),
                 Color::Ansi256(index) => Ansi256Color { index: *index },
+                Color::Ansi16(index) => Ansi16Color::new(*index).as_ansi256(),
             }
         }
+
+        fn as_ansi16(&self) -> Ansi16Color {
+            match self {
+                Color::Rgb(red, green, blue) => convert_rgb_into_ansi16(RgbColor {
+                    red: *red,
+                    green: *green,
+                    blue: *blue,
+                }),
+                Color::Ansi256(index) => Ansi256Color { index: *index }.as_ansi16(),
+                Color::Ansi16(index) => Ansi16Color::new(*index),
+            }
+        }
+    }
+
+    impl From<RgbColor> for Color {
+        fn from(rgb: RgbColor) -> Self {
+            Color::Rgb(rgb.red, rgb.green, rgb.blue)
+        }
+    }
+
+    impl From<Ansi256Color> for Color {
+        fn from(ansi256: Ansi256Color) -> Self {
+            Color::Ansi256(ansi256.index)
+        }
+    }
+
+    impl From<Ansi16Color> for Color {
+        fn from(ansi16: Ansi16Color) -> Self {
+            Color::Ansi16(ansi16.index())
+        }
     }
 }
 
@@ -76,7 +133,10 @@ pub struct RgbColor {
 }
 
 mod rgb_color_impl {
-    use crate::{convert_rgb_into_ansi256, Ansi256Color};
+    use crate::{
+        ansi256_conversion_strategy_get, convert_rgb_into_ansi16, convert_rgb_into_ansi256_cached,
+        Ansi16Color, Ansi256Color,
+    };
 
     use super::RgbColor;
     use super::TransformColor;
@@ -86,8 +146,15 @@ mod rgb_color_impl {
             *self
         }
 
+        /// Uses the global [Ansi256ConversionStrategy](crate::Ansi256ConversionStrategy),
+        /// which is [Redmean](crate::Ansi256ConversionStrategy::Redmean) by default. The
+        /// results are cached, see [convert_rgb_into_ansi256_cached].
         fn as_ansi256(&self) -> Ansi256Color {
-            convert_rgb_into_ansi256(*self)
+            convert_rgb_into_ansi256_cached(*self, ansi256_conversion_strategy_get())
+        }
+
+        fn as_ansi16(&self) -> Ansi16Color {
+            convert_rgb_into_ansi16(*self)
         }
     }
 }
@@ -98,16 +165,69 @@ pub struct Ansi256Color {
 }
 
 mod ansi_color_impl {
-    use crate::{constants::ANSI_COLOR_PALETTE, Ansi256Color, RgbColor, TransformColor};
+    use crate::{
+        convert_rgb_into_ansi16, with_active_palette, Ansi16Color, Ansi256Color, RgbColor,
+        TransformColor,
+    };
 
     impl TransformColor for Ansi256Color {
+        /// Uses the active [Palette](crate::Palette), which is
+        /// [Palette::XTERM](crate::Palette::XTERM) by default.
         fn as_rgb(&self) -> RgbColor {
-            let index = self.index as usize;
-            ANSI_COLOR_PALETTE[index].into()
+            with_active_palette(|palette| palette.rgb(*self))
         }
 
         fn as_ansi256(&self) -> Ansi256Color {
             *self
         }
+
+        fn as_ansi16(&self) -> Ansi16Color {
+            match self.index {
+                // The first 16 entries are the system colors.
+                0..=15 => Ansi16Color::new(self.index),
+                _ => convert_rgb_into_ansi16(self.as_rgb()),
+            }
+        }
+    }
+}
+
+/// One of the 16 system colors, which are the first 16 entries of the ANSI 256 color
+/// palette. These map to SGR codes 30–37, 90–97 (foreground) and 40–47, 100–107
+/// (background).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ansi16Color {
+    /// Always in the range `0..=15`, see [Ansi16Color::new].
+    index: u8,
+}
+
+mod ansi16_color_impl {
+    use crate::{Ansi16Color, Ansi256Color, RgbColor, TransformColor};
+
+    impl Ansi16Color {
+        /// Clamps `index` to the range `0..=15`, so a value that is too large becomes
+        /// bright white, instead of wrapping around to an unrelated color.
+        pub fn new(index: u8) -> Self {
+            Ansi16Color {
+                index: index.min(15),
+            }
+        }
+
+        pub fn index(&self) -> u8 {
+            self.index
+        }
+    }
+
+    impl TransformColor for Ansi16Color {
+        fn as_rgb(&self) -> RgbColor {
+            self.as_ansi256().as_rgb()
+        }
+
+        fn as_ansi256(&self) -> Ansi256Color {
+            Ansi256Color { index: self.index }
+        }
+
+        fn as_ansi16(&self) -> Ansi16Color {
+            *self
+        }
     }
 }
@@ -0,0 +1,492 @@
+/*
+ *   Copyright (c) 2023 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::*;
+use std::io::{ErrorKind, Result, Write};
+
+/// Escape sequences that are longer than this are passed through as is, instead of
+/// waiting for them to be terminated (eg: an OSC sequence that is never terminated).
+const MAX_PENDING_ESCAPE_LEN: usize = 4096;
+
+/// Wraps any [Write] and rewrites the SGR escape sequences that pass through it to fit
+/// the given [ColorSupport]. This is useful to pipe colored output from other programs
+/// (eg: `cargo`, `git`) through your own, while still honoring `NO_COLOR` and the
+/// terminal's capabilities.
+///
+/// - [ColorSupport::Truecolor] - everything is passed through as is.
+/// - [ColorSupport::Ansi256] - 24-bit colors (`38;2`, `48;2`) are converted to 8-bit
+///   colors (`38;5`, `48;5`) using [convert_rgb_into_ansi256].
+/// - [ColorSupport::Ansi16] - 24-bit and 8-bit colors are converted to the 16 system
+///   colors.
+/// - [ColorSupport::NoColor] - all SGR sequences are removed. Other escape sequences
+///   (eg: cursor movement) are passed through.
+///
+/// Escape sequences that are split across `write` calls are buffered until they are
+/// complete. If `inner` fails part way through writing the adapted output, the rest of
+/// it is buffered too, and written before anything else (or by [Write::flush]). When the
+/// writer is dropped, everything that is buffered is written (as is, and ignoring
+/// errors); use [ColorAdaptingWriter::into_inner] to find out about errors.
+///
+/// ## Example usage:
+///
+/// ```rust
+/// use r3bl_ansi_color::*;
+/// use std::io::Write;
+///
+/// let mut writer = ColorAdaptingWriter::new(vec![], ColorSupport::Ansi256);
+/// writer.write_all(b"\x1b[38;2;175;215;").unwrap();
+/// writer.write_all(b"135mHello\x1b[0m").unwrap();
+/// assert_eq!(writer.into_inner().unwrap(), b"\x1b[38;5;150mHello\x1b[0m");
+/// ```
+#[derive(Debug)]
+pub struct ColorAdaptingWriter<W: Write> {
+    writer: EscapeRewritingWriter<W>,
+    color_support: ColorSupport,
+}
+
+/// The buffering that [ColorAdaptingWriter] and [PassthroughWriter] share. It finds the
+/// escape sequences in what is written to it, and hands each complete one to a rewrite
+/// function.
+#[derive(Debug)]
+pub(crate) struct EscapeRewritingWriter<W: Write> {
+    /// Only [None] after [EscapeRewritingWriter::into_inner].
+    inner: Option<W>,
+    /// The start of an escape sequence that hasn't been terminated yet.
+    pending: Vec<u8>,
+    /// Rewritten output that `inner` hasn't accepted yet.
+    unwritten: Vec<u8>,
+    /// Escape sequences that are longer than this are passed through as is.
+    max_pending_len: usize,
+}
+
+mod color_adapting_writer_impl {
+    use super::*;
+
+    impl<W: Write> ColorAdaptingWriter<W> {
+        pub fn new(inner: W, color_support: ColorSupport) -> Self {
+            Self {
+                writer: EscapeRewritingWriter::new(inner, MAX_PENDING_ESCAPE_LEN),
+                color_support,
+            }
+        }
+
+        /// Uses the color support for `stream`, eg: to adapt output to whether stderr is
+        /// a TTY, when `inner` is [std::io::Stderr].
+        pub fn for_stream(inner: W, stream: Stream) -> Self {
+            Self::new(inner, query_runtime_color_support(stream))
+        }
+
+        pub fn color_support(&self) -> ColorSupport {
+            self.color_support
+        }
+
+        pub fn get_ref(&self) -> &W {
+            self.writer.get_ref()
+        }
+
+        pub fn get_mut(&mut self) -> &mut W {
+            self.writer.get_mut()
+        }
+
+        /// Writes everything that is still buffered, including an incomplete escape
+        /// sequence (as is), and returns the wrapped writer.
+        pub fn into_inner(self) -> Result<W> {
+            self.writer.into_inner()
+        }
+    }
+
+    impl<W: Write> Write for ColorAdaptingWriter<W> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            let color_support = self.color_support;
+            self.writer
+                .write_rewritten(buf, |sequence, original, output| match sequence {
+                    EscapeSequence::Csi {
+                        params,
+                        intermediates: [],
+                        final_byte: b'm',
+                    } => adapt_sgr_sequence(params, original, color_support, output),
+                    _ => output.extend_from_slice(original),
+                })
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            self.writer.flush()
+        }
+    }
+
+    impl<W: Write> EscapeRewritingWriter<W> {
+        pub(crate) fn new(inner: W, max_pending_len: usize) -> Self {
+            Self {
+                inner: Some(inner),
+                pending: vec![],
+                unwritten: vec![],
+                max_pending_len,
+            }
+        }
+
+        pub(crate) fn get_ref(&self) -> &W {
+            self.inner
+                .as_ref()
+                .expect("inner is only taken by into_inner")
+        }
+
+        pub(crate) fn get_mut(&mut self) -> &mut W {
+            self.inner
+                .as_mut()
+                .expect("inner is only taken by into_inner")
+        }
+
+        pub(crate) fn into_inner(mut self) -> Result<W> {
+            self.write_buffered()?;
+            Ok(self
+                .inner
+                .take()
+                .expect("inner is only taken by into_inner"))
+        }
+
+        /// Finds the escape sequences in `buf` (and in what is buffered from previous
+        /// calls), and calls `rewrite` w/ each complete one, and the bytes that make it
+        /// up. `rewrite` appends what to write instead to its `output` argument.
+        ///
+        /// Once the rewritten output is buffered, all of `buf` counts as written, even
+        /// if `inner` fails to take all of it. What's left is written by the next call
+        /// (which returns the error if it persists), or by [Write::flush].
+        pub(crate) fn write_rewritten(
+            &mut self,
+            buf: &[u8],
+            rewrite: impl FnMut(&EscapeSequence<'_>, &[u8], &mut Vec<u8>),
+        ) -> Result<usize> {
+            // If this fails, nothing from `buf` was consumed.
+            self.write_unwritten()?;
+
+            self.pending.extend_from_slice(buf);
+            let consumed = rewrite_escapes(
+                &self.pending,
+                self.max_pending_len,
+                rewrite,
+                &mut self.unwritten,
+            );
+            self.pending.drain(..consumed);
+
+            // `buf` is buffered now, so an error is only returned by the next call.
+            let _ = self.write_unwritten();
+            Ok(buf.len())
+        }
+
+        pub(crate) fn flush(&mut self) -> Result<()> {
+            self.write_unwritten()?;
+            self.get_mut().flush()
+        }
+
+        /// Writes [Self::unwritten], keeping track of how much of it `inner` accepted.
+        fn write_unwritten(&mut self) -> Result<()> {
+            let inner = self
+                .inner
+                .as_mut()
+                .expect("inner is only taken by into_inner");
+            while !self.unwritten.is_empty() {
+                match inner.write(&self.unwritten) {
+                    Ok(0) => {
+                        return Err(std::io::Error::new(
+                            ErrorKind::WriteZero,
+                            "failed to write the buffered output",
+                        ))
+                    }
+                    Ok(len) => {
+                        self.unwritten.drain(..len);
+                    }
+                    Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                    Err(error) => return Err(error),
+                }
+            }
+            Ok(())
+        }
+
+        /// Writes the unwritten output, and then the incomplete escape sequence (as is).
+        fn write_buffered(&mut self) -> Result<()> {
+            self.write_unwritten()?;
+            let pending = std::mem::take(&mut self.pending);
+            self.unwritten = pending;
+            self.write_unwritten()
+        }
+    }
+
+    impl<W: Write> Drop for EscapeRewritingWriter<W> {
+        fn drop(&mut self) {
+            if self.inner.is_some() {
+                let _ = self.write_buffered();
+            }
+        }
+    }
+
+    /// Rewrites the escape sequences in `input`, appending the result to `output`.
+    /// Returns the number of bytes of `input` that were consumed; the rest is an
+    /// incomplete escape sequence.
+    fn rewrite_escapes(
+        input: &[u8],
+        max_pending_len: usize,
+        mut rewrite: impl FnMut(&EscapeSequence<'_>, &[u8], &mut Vec<u8>),
+        output: &mut Vec<u8>,
+    ) -> usize {
+        let mut index = 0;
+
+        while index < input.len() {
+            let Some(offset) = input[index..].iter().position(|&it| it == ESC) else {
+                output.extend_from_slice(&input[index..]);
+                return input.len();
+            };
+            output.extend_from_slice(&input[index..index + offset]);
+            index += offset;
+
+            let remaining = &input[index..];
+            match recognize_escape(remaining) {
+                Recognized::Complete { sequence, len } => {
+                    rewrite(&sequence, &remaining[..len], output);
+                    index += len;
+                }
+                Recognized::Invalid { len } => {
+                    let len = len.max(1);
+                    output.extend_from_slice(&remaining[..len]);
+                    index += len;
+                }
+                Recognized::Incomplete if remaining.len() > max_pending_len => {
+                    output.extend_from_slice(remaining);
+                    return input.len();
+                }
+                Recognized::Incomplete => return index,
+            }
+        }
+
+        index
+    }
+
+    /// Rewrites a single SGR sequence. `params` are the parameters of the sequence, and
+    /// `original` is the whole sequence, which is emitted as is if nothing has to change.
+    /// Each color in it is adapted on its own, and the parameters that can't be parsed
+    /// are kept as is (in a sequence of their own), since the terminal might understand
+    /// them, eg: `4:3` (undercurl).
+    fn adapt_sgr_sequence(
+        params: &[u8],
+        original: &[u8],
+        color_support: ColorSupport,
+        output: &mut Vec<u8>,
+    ) {
+        if let ColorSupport::NoColor = color_support {
+            return;
+        }
+
+        let params = std::str::from_utf8(params).unwrap_or_default();
+        let mut is_changed = false;
+        let adapted: Vec<std::result::Result<Vec<SgrCode>, &str>> = split_sgr_params(params)
+            .into_iter()
+            .map(|param| {
+                let sgr_codes = parse_sgr_params(param).map_err(|_| param)?;
+                let adapted: Vec<SgrCode> = sgr_codes
+                    .iter()
+                    .map(|it| adapt_sgr_code(*it, color_support))
+                    .collect();
+                is_changed |= adapted != sgr_codes;
+                Ok(adapted)
+            })
+            .collect();
+        if !is_changed {
+            output.extend_from_slice(original);
+            return;
+        }
+
+        for it in adapted {
+            match it {
+                Ok(sgr_codes) => {
+                    for sgr_code in sgr_codes {
+                        output.extend_from_slice(sgr_code.to_string().as_bytes());
+                    }
+                }
+                Err(param) => output.extend_from_slice(format!("\x1b[{param}m").as_bytes()),
+            }
+        }
+    }
+
+    /// Converts the color in `sgr_code` (if any) to fit `color_support`.
+    fn adapt_sgr_code(sgr_code: SgrCode, color_support: ColorSupport) -> SgrCode {
+        match (color_support, sgr_code) {
+            (ColorSupport::Ansi256, SgrCode::ForegroundRGB(red, green, blue)) => {
+                SgrCode::ForegroundAnsi256(RgbColor { red, green, blue }.as_ansi256().index)
+            }
+            (ColorSupport::Ansi256, SgrCode::BackgroundRGB(red, green, blue)) => {
+                SgrCode::BackgroundAnsi256(RgbColor { red, green, blue }.as_ansi256().index)
+            }
+            (ColorSupport::Ansi16, SgrCode::ForegroundRGB(red, green, blue)) => {
+                SgrCode::ForegroundAnsi16(RgbColor { red, green, blue }.as_ansi16().index())
+            }
+            (ColorSupport::Ansi16, SgrCode::BackgroundRGB(red, green, blue)) => {
+                SgrCode::BackgroundAnsi16(RgbColor { red, green, blue }.as_ansi16().index())
+            }
+            (ColorSupport::Ansi16, SgrCode::ForegroundAnsi256(index)) => {
+                SgrCode::ForegroundAnsi16(Ansi256Color { index }.as_ansi16().index())
+            }
+            (ColorSupport::Ansi16, SgrCode::BackgroundAnsi256(index)) => {
+                SgrCode::BackgroundAnsi16(Ansi256Color { index }.as_ansi16().index())
+            }
+            _ => sgr_code,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const INPUT: &str = "\x1b[1;38;2;175;215;135mbold\x1b[0m \x1b[48;5;196mred\x1b[0m \x1b[2Kdone";
+
+    fn adapt(color_support: ColorSupport, chunk_size: usize) -> String {
+        let mut writer = ColorAdaptingWriter::new(vec![], color_support);
+        for chunk in INPUT.as_bytes().chunks(chunk_size) {
+            writer.write_all(chunk).unwrap();
+        }
+        String::from_utf8(writer.into_inner().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_truecolor_passes_through() {
+        assert_eq!(adapt(ColorSupport::Truecolor, INPUT.len()), INPUT);
+    }
+
+    #[test]
+    fn test_downgrade_to_ansi256() {
+        assert_eq!(
+            adapt(ColorSupport::Ansi256, INPUT.len()),
+            "\x1b[1m\x1b[38;5;150mbold\x1b[0m \x1b[48;5;196mred\x1b[0m \x1b[2Kdone"
+        );
+    }
+
+    #[test]
+    fn test_downgrade_to_ansi16() {
+        assert_eq!(
+            adapt(ColorSupport::Ansi16, INPUT.len()),
+            "\x1b[1m\x1b[37mbold\x1b[0m \x1b[101mred\x1b[0m \x1b[2Kdone"
+        );
+    }
+
+    #[test]
+    fn test_strip_for_no_color() {
+        assert_eq!(
+            adapt(ColorSupport::NoColor, INPUT.len()),
+            "bold red \x1b[2Kdone"
+        );
+    }
+
+    #[test]
+    fn test_sequences_split_across_writes() {
+        for chunk_size in 1..INPUT.len() {
+            assert_eq!(
+                adapt(ColorSupport::Ansi256, chunk_size),
+                adapt(ColorSupport::Ansi256, INPUT.len())
+            );
+            assert_eq!(
+                adapt(ColorSupport::NoColor, chunk_size),
+                adapt(ColorSupport::NoColor, INPUT.len())
+            );
+        }
+    }
+
+    /// Accepts at most `budget` bytes in total, and then fails (once).
+    struct FlakyWriter {
+        written: Vec<u8>,
+        budget: usize,
+        failed: bool,
+    }
+
+    impl Write for FlakyWriter {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            if self.written.len() >= self.budget && !self.failed {
+                self.failed = true;
+                return Err(ErrorKind::Other.into());
+            }
+            let len = if self.failed {
+                buf.len()
+            } else {
+                buf.len().min(self.budget - self.written.len())
+            };
+            self.written.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_partial_writes_are_not_duplicated() {
+        let expected = adapt(ColorSupport::Ansi256, INPUT.len());
+        for budget in 0..expected.len() {
+            let inner = FlakyWriter {
+                written: vec![],
+                budget,
+                failed: false,
+            };
+            let mut writer = ColorAdaptingWriter::new(inner, ColorSupport::Ansi256);
+            for chunk in INPUT.as_bytes().chunks(7) {
+                // Retry like `write_all` does, after an error.
+                while writer.write(chunk).is_err() {}
+            }
+            writer.flush().unwrap();
+            let written = writer.into_inner().unwrap().written;
+            assert_eq!(String::from_utf8(written).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_drop_writes_what_is_buffered() {
+        let mut output = vec![];
+        {
+            let mut writer = ColorAdaptingWriter::new(&mut output, ColorSupport::Ansi256);
+            writer.write_all(b"done\x1b[38;2;1").unwrap();
+        }
+        assert_eq!(output, b"done\x1b[38;2;1");
+    }
+
+    #[test]
+    fn test_unknown_sgr_params_pass_through() {
+        let mut writer = ColorAdaptingWriter::new(vec![], ColorSupport::Ansi256);
+        writer.write_all(b"\x1b[21mdouble underline").unwrap();
+        assert_eq!(writer.into_inner().unwrap(), b"\x1b[21mdouble underline");
+    }
+
+    #[test]
+    fn test_unknown_sgr_params_next_to_colors() {
+        let input = b"\x1b[4:3;38;2;255;0;0;58:5:1mundercurl";
+        let mut writer = ColorAdaptingWriter::new(vec![], ColorSupport::Ansi256);
+        writer.write_all(input).unwrap();
+        assert_eq!(
+            writer.into_inner().unwrap(),
+            b"\x1b[4:3m\x1b[38;5;196m\x1b[58:5:1mundercurl"
+        );
+
+        let mut writer = ColorAdaptingWriter::new(vec![], ColorSupport::Ansi16);
+        writer.write_all(input).unwrap();
+        assert_eq!(
+            writer.into_inner().unwrap(),
+            b"\x1b[4:3m\x1b[91m\x1b[58:5:1mundercurl"
+        );
+
+        let mut writer = ColorAdaptingWriter::new(vec![], ColorSupport::Truecolor);
+        writer.write_all(input).unwrap();
+        assert_eq!(writer.into_inner().unwrap(), input);
+    }
+}
@@ -0,0 +1,189 @@
+/*
+ *   Copyright (c) 2023 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+//! Operations to derive new colors from existing ones (eg: hover or dimmed variants of a
+//! theme color). They are available on every type that implements [TransformColor], and
+//! they always return a [RgbColor] (which converts into a [Color]).
+//!
+//! The operations work in perceptual color spaces, so eg: lightening yellow and blue by
+//! the same amount looks like the same change:
+//! - Lightness and saturation are changed in [OklchColor], and the result is gamut mapped.
+//! - Mixing is done in [Oklab].
+//! - Alpha blending is done in linear light, which is how light actually mixes.
+//!
+//! ## Example usage:
+//!
+//! ```rust
+//! use r3bl_ansi_color::*;
+//!
+//! let accent = RgbColor::from(0x5fafd7);
+//! let hover = accent.lighten(0.1);
+//! let disabled = accent.desaturate(0.8);
+//! let selection = accent.with_alpha_over(RgbColor::from(0x1e1e1e), 0.3);
+//! ```
+
+use crate::*;
+
+pub trait ManipulateColor: TransformColor {
+    /// Increases the OKLCH lightness by `amount`, where the lightness ranges from `0.0`
+    /// (black) to `1.0` (white). Eg: `lighten(0.1)` makes a color 10% lighter.
+    fn lighten(&self, amount: f32) -> RgbColor {
+        let oklch = OklchColor::from(self.as_rgb());
+        OklchColor::new(oklch.lightness + amount, oklch.chroma, oklch.hue).into()
+    }
+
+    /// Decreases the OKLCH lightness by `amount`, see [ManipulateColor::lighten].
+    fn darken(&self, amount: f32) -> RgbColor {
+        self.lighten(-amount)
+    }
+
+    /// Scales the OKLCH chroma by `1.0 + amount`. Eg: `saturate(0.5)` makes a color 50%
+    /// more colorful. Grays stay gray.
+    fn saturate(&self, amount: f32) -> RgbColor {
+        let oklch = OklchColor::from(self.as_rgb());
+        let chroma = (oklch.chroma * (1.0 + amount)).max(0.0);
+        OklchColor::new(oklch.lightness, chroma, oklch.hue).into()
+    }
+
+    /// Scales the OKLCH chroma by `1.0 - amount`, so `desaturate(1.0)` is the same as
+    /// [ManipulateColor::grayscale].
+    fn desaturate(&self, amount: f32) -> RgbColor {
+        self.saturate(-amount)
+    }
+
+    /// Interpolates between `self` (when `t` is `0.0`) and `other` (when `t` is `1.0`) in
+    /// OKLab. `t` is clamped to `0.0..=1.0`.
+    fn mix(&self, other: impl TransformColor, t: f32) -> RgbColor {
+        let t = t.clamp(0.0, 1.0);
+        let (from, to) = (Oklab::from(self.as_rgb()), Oklab::from(other.as_rgb()));
+        Oklab {
+            l: from.l + (to.l - from.l) * t,
+            a: from.a + (to.a - from.a) * t,
+            b: from.b + (to.b - from.b) * t,
+        }
+        .into()
+    }
+
+    /// Inverts each sRGB channel, eg: white becomes black.
+    fn invert(&self) -> RgbColor {
+        let RgbColor { red, green, blue } = self.as_rgb();
+        RgbColor {
+            red: 255 - red,
+            green: 255 - green,
+            blue: 255 - blue,
+        }
+    }
+
+    /// Removes all the chroma, while keeping the perceived lightness.
+    fn grayscale(&self) -> RgbColor {
+        let oklch = OklchColor::from(self.as_rgb());
+        OklchColor::new(oklch.lightness, 0.0, 0.0).into()
+    }
+
+    /// Returns what `self` looks like when it is drawn w/ the given `alpha` (`0.0` is
+    /// transparent, `1.0` is opaque) on top of `background`. `alpha` is clamped to
+    /// `0.0..=1.0`.
+    fn with_alpha_over(&self, background: impl TransformColor, alpha: f32) -> RgbColor {
+        let alpha = alpha.clamp(0.0, 1.0);
+        let (foreground, background) = (self.as_rgb(), background.as_rgb());
+        let blend = |foreground: u8, background: u8| {
+            let (foreground, background) = (srgb_to_linear(foreground), srgb_to_linear(background));
+            linear_to_srgb(foreground * alpha + background * (1.0 - alpha))
+        };
+        RgbColor {
+            red: blend(foreground.red, background.red),
+            green: blend(foreground.green, background.green),
+            blue: blend(foreground.blue, background.blue),
+        }
+    }
+}
+
+impl<T: TransformColor> ManipulateColor for T {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const ACCENT: RgbColor = RgbColor {
+        red: 0x5f,
+        green: 0xaf,
+        blue: 0xd7,
+    };
+
+    fn oklch(rgb: RgbColor) -> OklchColor {
+        rgb.into()
+    }
+
+    #[test]
+    fn test_lighten_and_darken() {
+        assert_eq!(ACCENT.lighten(0.0), ACCENT);
+        assert!(
+            (oklch(ACCENT.lighten(0.1)).lightness - oklch(ACCENT).lightness - 0.1).abs() < 0.01
+        );
+        assert!((oklch(ACCENT.darken(0.1)).lightness - oklch(ACCENT).lightness + 0.1).abs() < 0.01);
+        // The hue is kept.
+        assert!((oklch(ACCENT.darken(0.2)).hue - oklch(ACCENT).hue).abs() < 2.0);
+        assert_eq!(ACCENT.lighten(1.0), RgbColor::from(0xffffff));
+        assert_eq!(ACCENT.darken(1.0), RgbColor::from(0x000000));
+    }
+
+    #[test]
+    fn test_saturate_and_desaturate() {
+        assert!(oklch(ACCENT.saturate(0.3)).chroma > oklch(ACCENT).chroma);
+        assert!(oklch(ACCENT.desaturate(0.3)).chroma < oklch(ACCENT).chroma);
+        assert_eq!(ACCENT.desaturate(1.0), ACCENT.grayscale());
+        let gray = RgbColor::from(0x808080);
+        assert_eq!(gray.saturate(1.0), gray);
+    }
+
+    #[test]
+    fn test_mix() {
+        let black = RgbColor::from(0x000000);
+        let white = RgbColor::from(0xffffff);
+        assert_eq!(black.mix(white, 0.0), black);
+        assert_eq!(black.mix(white, 1.0), white);
+        // OKLCH lightness 0.5 is perceptual middle gray, which is darker than the sRGB
+        // average (0x808080).
+        assert_eq!(black.mix(white, 0.5), RgbColor::from(0x636363));
+        assert_eq!(
+            Color::Ansi256(196).mix(Color::Ansi256(21), 2.0),
+            RgbColor::from(0x0000ff)
+        );
+    }
+
+    #[test]
+    fn test_invert_and_grayscale() {
+        assert_eq!(ACCENT.invert(), RgbColor::from(0xa05028));
+        assert_eq!(Color::Ansi16(0).invert(), RgbColor::from(0xffffff));
+
+        let gray = ACCENT.grayscale();
+        assert_eq!(gray.red, gray.green);
+        assert_eq!(gray.green, gray.blue);
+        assert!((oklch(gray).lightness - oklch(ACCENT).lightness).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_with_alpha_over() {
+        let white = RgbColor::from(0xffffff);
+        let black = RgbColor::from(0x000000);
+        assert_eq!(white.with_alpha_over(black, 1.0), white);
+        assert_eq!(white.with_alpha_over(black, 0.0), black);
+        // Blending in linear light, 50% of white is brighter than 0x808080.
+        assert_eq!(white.with_alpha_over(black, 0.5), RgbColor::from(0xbcbcbc));
+    }
+}
@@ -0,0 +1,470 @@
+/*
+ *   Copyright (c) 2023 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+//! Cylindrical color models, which are easier for humans to reason about than RGB. All
+//! of them convert into [Color::Rgb], so they can be used wherever a [Color] is.
+//!
+//! More info:
+//! - <https://en.wikipedia.org/wiki/HSL_and_HSV>
+//! - <https://bottosson.github.io/posts/oklab/#the-oklab-color-space>
+//! - <https://www.w3.org/TR/css-color-4/#css-gamut-mapping>
+
+use crate::*;
+
+/// A color in the HSL color model.
+/// - `hue` is in degrees, and it wraps around (eg: `-30.0` is the same as `330.0`).
+/// - `saturation` and `lightness` are in `0.0..=1.0`, and they are clamped to it.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct HslColor {
+    pub hue: f32,
+    pub saturation: f32,
+    pub lightness: f32,
+}
+
+/// A color in the HSV (aka HSB) color model.
+/// - `hue` is in degrees, and it wraps around.
+/// - `saturation` and `value` are in `0.0..=1.0`, and they are clamped to it.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct HsvColor {
+    pub hue: f32,
+    pub saturation: f32,
+    pub value: f32,
+}
+
+/// A color in the OKLCH color space, which is the polar form of [Oklab].
+/// - `lightness` is in `0.0..=1.0`.
+/// - `chroma` is `0.0` for grays, and it is about `0.37` at most for sRGB colors.
+/// - `hue` is in degrees, and it wraps around.
+///
+/// Colors that are out of the sRGB gamut are mapped into it by reducing `chroma`, while
+/// keeping `lightness` and `hue`. This keeps the hue stable, unlike clipping each RGB
+/// channel separately.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct OklchColor {
+    pub lightness: f32,
+    pub chroma: f32,
+    pub hue: f32,
+}
+
+/// Returns the hue (in degrees), the max component, and the min component of `rgb`,
+/// where each component is in `0.0..=1.0`. This is shared by HSL and HSV.
+fn hue_max_min(rgb: RgbColor) -> (f32, f32, f32) {
+    let (red, green, blue) = (
+        rgb.red as f32 / 255.0,
+        rgb.green as f32 / 255.0,
+        rgb.blue as f32 / 255.0,
+    );
+    let max = red.max(green).max(blue);
+    let min = red.min(green).min(blue);
+    let delta = max - min;
+
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == red {
+        60.0 * ((green - blue) / delta).rem_euclid(6.0)
+    } else if max == green {
+        60.0 * ((blue - red) / delta + 2.0)
+    } else {
+        60.0 * ((red - green) / delta + 4.0)
+    };
+
+    (hue, max, min)
+}
+
+/// Converts a chroma, hue and the smallest component to RGB. This is shared by HSL and
+/// HSV, which only differ in how they compute the chroma and the smallest component.
+fn rgb_from_chroma_hue_min(chroma: f32, hue: f32, min: f32) -> RgbColor {
+    let hue = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (hue.rem_euclid(2.0) - 1.0).abs());
+
+    let (red, green, blue) = match hue as u8 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+
+    let to_u8 = |it: f32| ((it + min) * 255.0).round().clamp(0.0, 255.0) as u8;
+    RgbColor {
+        red: to_u8(red),
+        green: to_u8(green),
+        blue: to_u8(blue),
+    }
+}
+
+mod hsl_color_impl {
+    use super::*;
+
+    impl HslColor {
+        pub fn new(hue: f32, saturation: f32, lightness: f32) -> Self {
+            Self {
+                hue,
+                saturation,
+                lightness,
+            }
+        }
+    }
+
+    impl From<RgbColor> for HslColor {
+        fn from(rgb: RgbColor) -> Self {
+            let (hue, max, min) = hue_max_min(rgb);
+            let lightness = (max + min) / 2.0;
+            let saturation = if max == min {
+                0.0
+            } else {
+                (max - min) / (1.0 - (2.0 * lightness - 1.0).abs())
+            };
+            HslColor {
+                hue,
+                saturation,
+                lightness,
+            }
+        }
+    }
+
+    impl From<HslColor> for RgbColor {
+        fn from(hsl: HslColor) -> Self {
+            let saturation = hsl.saturation.clamp(0.0, 1.0);
+            let lightness = hsl.lightness.clamp(0.0, 1.0);
+            let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+            rgb_from_chroma_hue_min(chroma, hsl.hue, lightness - chroma / 2.0)
+        }
+    }
+
+    impl From<HslColor> for Color {
+        fn from(hsl: HslColor) -> Self {
+            RgbColor::from(hsl).into()
+        }
+    }
+
+    impl TransformColor for HslColor {
+        fn as_rgb(&self) -> RgbColor {
+            (*self).into()
+        }
+
+        fn as_ansi256(&self) -> Ansi256Color {
+            self.as_rgb().as_ansi256()
+        }
+    }
+}
+
+mod hsv_color_impl {
+    use super::*;
+
+    impl HsvColor {
+        pub fn new(hue: f32, saturation: f32, value: f32) -> Self {
+            Self {
+                hue,
+                saturation,
+                value,
+            }
+        }
+    }
+
+    impl From<RgbColor> for HsvColor {
+        fn from(rgb: RgbColor) -> Self {
+            let (hue, max, min) = hue_max_min(rgb);
+            let saturation = if max == 0.0 { 0.0 } else { (max - min) / max };
+            HsvColor {
+                hue,
+                saturation,
+                value: max,
+            }
+        }
+    }
+
+    impl From<HsvColor> for RgbColor {
+        fn from(hsv: HsvColor) -> Self {
+            let saturation = hsv.saturation.clamp(0.0, 1.0);
+            let value = hsv.value.clamp(0.0, 1.0);
+            let chroma = value * saturation;
+            rgb_from_chroma_hue_min(chroma, hsv.hue, value - chroma)
+        }
+    }
+
+    impl From<HsvColor> for Color {
+        fn from(hsv: HsvColor) -> Self {
+            RgbColor::from(hsv).into()
+        }
+    }
+
+    impl TransformColor for HsvColor {
+        fn as_rgb(&self) -> RgbColor {
+            (*self).into()
+        }
+
+        fn as_ansi256(&self) -> Ansi256Color {
+            self.as_rgb().as_ansi256()
+        }
+    }
+}
+
+mod oklch_color_impl {
+    use super::*;
+
+    /// How far out of `0.0..=1.0` a linear RGB component can be, and still count as in
+    /// gamut. This absorbs the rounding errors of the OKLab matrices.
+    const GAMUT_EPSILON: f32 = 0.000_1;
+
+    /// When to stop the binary search for the largest chroma that is in gamut.
+    const CHROMA_EPSILON: f32 = 0.000_1;
+
+    /// Every color in sRGB has a chroma below about `0.32`, so anything above this is out
+    /// of gamut.
+    const MAX_SRGB_CHROMA: f32 = 0.5;
+
+    /// `MAX_SRGB_CHROMA / 2^32` is way below [CHROMA_EPSILON].
+    const MAX_GAMUT_MAPPING_STEPS: usize = 32;
+
+    impl OklchColor {
+        pub fn new(lightness: f32, chroma: f32, hue: f32) -> Self {
+            Self {
+                lightness,
+                chroma,
+                hue,
+            }
+        }
+
+        pub fn as_oklab(&self) -> Oklab {
+            let (sin, cos) = self.hue.to_radians().sin_cos();
+            Oklab {
+                l: self.lightness,
+                a: self.chroma * cos,
+                b: self.chroma * sin,
+            }
+        }
+
+        pub fn is_in_srgb_gamut(&self) -> bool {
+            let (red, green, blue) = self.as_oklab().to_linear_rgb();
+            [red, green, blue]
+                .iter()
+                .all(|it| (-GAMUT_EPSILON..=1.0 + GAMUT_EPSILON).contains(it))
+        }
+
+        /// Returns the closest color that is in the sRGB gamut, w/ the same lightness and
+        /// hue. The chroma is reduced (using a binary search) until the color fits.
+        /// Components that are not finite (eg: `NaN`, or an infinite chroma) are treated
+        /// as `0`.
+        pub fn gamut_mapped(&self) -> OklchColor {
+            let finite_or_zero = |it: f32| if it.is_finite() { it } else { 0.0 };
+            let lightness = finite_or_zero(self.lightness);
+            let hue = finite_or_zero(self.hue);
+
+            // Only black and white exist at the ends of the lightness range.
+            if lightness <= 0.0 || lightness >= 1.0 {
+                return OklchColor::new(lightness.clamp(0.0, 1.0), 0.0, hue);
+            }
+            let chroma = finite_or_zero(self.chroma).clamp(0.0, MAX_SRGB_CHROMA);
+            let mapped = OklchColor::new(lightness, chroma, hue);
+            if mapped.is_in_srgb_gamut() {
+                return mapped;
+            }
+
+            let (mut low, mut high) = (0.0, chroma);
+            for _ in 0..MAX_GAMUT_MAPPING_STEPS {
+                if high - low <= CHROMA_EPSILON {
+                    break;
+                }
+                let mid = (low + high) / 2.0;
+                if OklchColor::new(lightness, mid, hue).is_in_srgb_gamut() {
+                    low = mid;
+                } else {
+                    high = mid;
+                }
+            }
+            OklchColor::new(lightness, low, hue)
+        }
+    }
+
+    impl From<Oklab> for OklchColor {
+        fn from(oklab: Oklab) -> Self {
+            let chroma = (oklab.a * oklab.a + oklab.b * oklab.b).sqrt();
+            let hue = if chroma == 0.0 {
+                0.0
+            } else {
+                oklab.b.atan2(oklab.a).to_degrees().rem_euclid(360.0)
+            };
+            OklchColor {
+                lightness: oklab.l,
+                chroma,
+                hue,
+            }
+        }
+    }
+
+    impl From<OklchColor> for Oklab {
+        fn from(oklch: OklchColor) -> Self {
+            oklch.as_oklab()
+        }
+    }
+
+    impl From<RgbColor> for OklchColor {
+        fn from(rgb: RgbColor) -> Self {
+            Oklab::from(rgb).into()
+        }
+    }
+
+    /// Out of gamut colors are gamut mapped, see [OklchColor::gamut_mapped].
+    impl From<OklchColor> for RgbColor {
+        fn from(oklch: OklchColor) -> Self {
+            oklch.gamut_mapped().as_oklab().into()
+        }
+    }
+
+    impl From<OklchColor> for Color {
+        fn from(oklch: OklchColor) -> Self {
+            RgbColor::from(oklch).into()
+        }
+    }
+
+    impl TransformColor for OklchColor {
+        fn as_rgb(&self) -> RgbColor {
+            (*self).into()
+        }
+
+        fn as_ansi256(&self) -> Ansi256Color {
+            self.as_rgb().as_ansi256()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 0.01,
+            "{actual} is not close to {expected}"
+        );
+    }
+
+    #[test]
+    fn test_hsl() {
+        let hsl = HslColor::from(RgbColor::from(0x00ffff));
+        assert_close(hsl.hue, 180.0);
+        assert_close(hsl.saturation, 1.0);
+        assert_close(hsl.lightness, 0.5);
+
+        assert_eq!(
+            RgbColor::from(HslColor::new(210.0, 0.5, 0.25)),
+            RgbColor::from(0x204060)
+        );
+        // Hue wraps around, and saturation and lightness are clamped.
+        assert_eq!(
+            RgbColor::from(HslColor::new(-360.0, 2.0, 0.5)),
+            RgbColor::from(0xff0000)
+        );
+        assert_eq!(
+            Color::from(HslColor::new(120.0, 1.0, 0.5)),
+            Color::Rgb(0, 255, 0)
+        );
+        // The default `as_ansi16`, which goes through `as_rgb`.
+        assert_eq!(
+            HslColor::new(120.0, 1.0, 0.5).as_ansi16(),
+            RgbColor::from(0x00ff00).as_ansi16()
+        );
+    }
+
+    #[test]
+    fn test_hsv() {
+        let hsv = HsvColor::from(RgbColor::from(0x336699));
+        assert_close(hsv.hue, 210.0);
+        assert_close(hsv.saturation, 0.667);
+        assert_close(hsv.value, 0.6);
+
+        assert_eq!(
+            RgbColor::from(HsvColor::new(300.0, 1.0, 1.0)),
+            RgbColor::from(0xff00ff)
+        );
+        assert_eq!(
+            HsvColor::new(0.0, 0.0, 1.0).as_ansi256(),
+            Ansi256Color { index: 231 }
+        );
+    }
+
+    #[test]
+    fn test_rgb_round_trips() {
+        for red in (0..=255).step_by(15) {
+            for green in (0..=255).step_by(15) {
+                for blue in (0..=255).step_by(15) {
+                    let rgb = RgbColor { red, green, blue };
+                    assert_eq!(RgbColor::from(HslColor::from(rgb)), rgb);
+                    assert_eq!(RgbColor::from(HsvColor::from(rgb)), rgb);
+                    assert_eq!(RgbColor::from(OklchColor::from(rgb)), rgb);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_oklch() {
+        let oklch = OklchColor::from(RgbColor::from(0xff0000));
+        assert_close(oklch.lightness, 0.628);
+        assert_close(oklch.chroma, 0.258);
+        assert_close(oklch.hue, 29.23);
+        assert!(oklch.is_in_srgb_gamut());
+    }
+
+    #[test]
+    fn test_oklch_gamut_mapping_keeps_hue() {
+        // A very saturated green, way outside of sRGB.
+        let out_of_gamut = OklchColor::new(0.7, 0.4, 145.0);
+        assert!(!out_of_gamut.is_in_srgb_gamut());
+
+        let mapped = out_of_gamut.gamut_mapped();
+        assert!(mapped.is_in_srgb_gamut());
+        assert!(mapped.chroma < out_of_gamut.chroma);
+        assert_eq!(mapped.lightness, out_of_gamut.lightness);
+        assert_eq!(mapped.hue, out_of_gamut.hue);
+
+        // The hue of the resulting RGB color stays close, unlike clipping each channel.
+        let rgb = RgbColor::from(out_of_gamut);
+        let round_trip = OklchColor::from(rgb);
+        assert!((round_trip.hue - 145.0).abs() < 1.0, "{round_trip:?}");
+        assert!((round_trip.lightness - 0.7).abs() < 0.01, "{round_trip:?}");
+
+        let clipped = RgbColor::from(out_of_gamut.as_oklab());
+        assert!((OklchColor::from(clipped).hue - 145.0).abs() > 1.0);
+    }
+
+    #[test]
+    fn test_oklch_gamut_mapping_non_finite() {
+        let infinite_chroma = OklchColor::new(0.5, f32::INFINITY, 0.0).gamut_mapped();
+        assert_eq!(infinite_chroma, OklchColor::new(0.5, 0.0, 0.0));
+        let huge_chroma = OklchColor::new(0.5, f32::MAX, 0.0).gamut_mapped();
+        assert!(huge_chroma.is_in_srgb_gamut());
+        assert!(huge_chroma.chroma > 0.0);
+
+        let nan = OklchColor::new(f32::NAN, f32::NAN, f32::NAN).gamut_mapped();
+        assert_eq!(nan, OklchColor::new(0.0, 0.0, 0.0));
+        let nan_chroma = OklchColor::new(0.5, f32::NAN, 90.0).gamut_mapped();
+        assert_eq!(nan_chroma, OklchColor::new(0.5, 0.0, 90.0));
+
+        assert_eq!(
+            RgbColor::from(OklchColor::new(0.5, f32::INFINITY, 0.0)),
+            RgbColor::from(infinite_chroma)
+        );
+        assert_eq!(
+            RgbColor::from(OklchColor::new(f32::NAN, 0.1, 0.0)),
+            RgbColor::from(0x000000)
+        );
+    }
+}
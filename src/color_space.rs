@@ -0,0 +1,319 @@
+/*
+ *   Copyright (c) 2023 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+//! Perceptual color spaces, which are used to measure how different 2 colors look.
+//!
+//! More info:
+//! - <https://bottosson.github.io/posts/oklab/>
+//! - <https://en.wikipedia.org/wiki/CIELAB_color_space>
+//! - <https://en.wikipedia.org/wiki/Color_difference#CIEDE2000>
+//! - <https://hajim.rochester.edu/ece/sites/gsharma/ciede2000/ciede2000noteCRNA.pdf>
+
+use crate::RgbColor;
+
+/// Converts a sRGB component (`0..=255`) to linear light (`0.0..=1.0`).
+pub fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts a linear light component (`0.0..=1.0`) to sRGB (`0..=255`). Values outside
+/// of `0.0..=1.0` are clamped.
+pub fn linear_to_srgb(value: f32) -> u8 {
+    let value = value.clamp(0.0, 1.0);
+    let value = if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    (value * 255.0).round() as u8
+}
+
+/// A color in the OKLab color space, where `l` is the perceived lightness (`0.0..=1.0`),
+/// and `a` (green to red) and `b` (blue to yellow) are the opponent axes.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Oklab {
+    pub l: f32,
+    pub a: f32,
+    pub b: f32,
+}
+
+mod oklab_impl {
+    use super::*;
+
+    impl Oklab {
+        /// Converts linear sRGB (each component in `0.0..=1.0`) to OKLab.
+        pub fn from_linear_rgb(red: f32, green: f32, blue: f32) -> Oklab {
+            let l = 0.412_221_46 * red + 0.536_332_55 * green + 0.051_445_995 * blue;
+            let m = 0.211_903_5 * red + 0.680_699_5 * green + 0.107_396_96 * blue;
+            let s = 0.088_302_46 * red + 0.281_718_85 * green + 0.629_978_7 * blue;
+
+            let (l, m, s) = (l.cbrt(), m.cbrt(), s.cbrt());
+
+            Oklab {
+                l: 0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+                a: 1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+                b: 0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+            }
+        }
+
+        /// Converts `self` to linear sRGB. The components may be outside of `0.0..=1.0`
+        /// if `self` is out of the sRGB gamut.
+        pub fn to_linear_rgb(&self) -> (f32, f32, f32) {
+            let l = self.l + 0.396_337_78 * self.a + 0.215_803_76 * self.b;
+            let m = self.l - 0.105_561_346 * self.a - 0.063_854_17 * self.b;
+            let s = self.l - 0.089_484_18 * self.a - 1.291_485_5 * self.b;
+
+            let (l, m, s) = (l * l * l, m * m * m, s * s * s);
+
+            (
+                4.076_741_7 * l - 3.307_711_6 * m + 0.230_969_94 * s,
+                -1.268_438 * l + 2.609_757_4 * m - 0.341_319_38 * s,
+                -0.004_196_086_3 * l - 0.703_418_6 * m + 1.707_614_7 * s,
+            )
+        }
+
+        /// Squared euclidean distance between 2 colors, which is good enough to compare
+        /// distances (since OKLab is perceptually uniform).
+        pub fn distance_squared(&self, other: &Oklab) -> f32 {
+            let (dl, da, db) = (self.l - other.l, self.a - other.a, self.b - other.b);
+            dl * dl + da * da + db * db
+        }
+    }
+
+    impl From<RgbColor> for Oklab {
+        fn from(rgb: RgbColor) -> Self {
+            Oklab::from_linear_rgb(
+                srgb_to_linear(rgb.red),
+                srgb_to_linear(rgb.green),
+                srgb_to_linear(rgb.blue),
+            )
+        }
+    }
+
+    /// Components that are out of the sRGB gamut are clamped.
+    impl From<Oklab> for RgbColor {
+        fn from(oklab: Oklab) -> Self {
+            let (red, green, blue) = oklab.to_linear_rgb();
+            RgbColor {
+                red: linear_to_srgb(red),
+                green: linear_to_srgb(green),
+                blue: linear_to_srgb(blue),
+            }
+        }
+    }
+}
+
+/// A color in the CIE L\*a\*b\* color space (D65 white point), where `l` is the
+/// lightness (`0.0..=100.0`).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CieLab {
+    pub l: f32,
+    pub a: f32,
+    pub b: f32,
+}
+
+mod cielab_impl {
+    use super::*;
+
+    /// D65 reference white.
+    const WHITE_X: f32 = 0.950_47;
+    const WHITE_Y: f32 = 1.0;
+    const WHITE_Z: f32 = 1.088_83;
+
+    impl From<RgbColor> for CieLab {
+        fn from(rgb: RgbColor) -> Self {
+            let (red, green, blue) = (
+                srgb_to_linear(rgb.red),
+                srgb_to_linear(rgb.green),
+                srgb_to_linear(rgb.blue),
+            );
+
+            let x = 0.412_456_4 * red + 0.357_576_1 * green + 0.180_437_5 * blue;
+            let y = 0.212_672_9 * red + 0.715_152_2 * green + 0.072_175 * blue;
+            let z = 0.019_333_9 * red + 0.119_192 * green + 0.950_304_1 * blue;
+
+            let f = |t: f32| {
+                const DELTA: f32 = 6.0 / 29.0;
+                if t > DELTA * DELTA * DELTA {
+                    t.cbrt()
+                } else {
+                    t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+                }
+            };
+            let (fx, fy, fz) = (f(x / WHITE_X), f(y / WHITE_Y), f(z / WHITE_Z));
+
+            CieLab {
+                l: 116.0 * fy - 16.0,
+                a: 500.0 * (fx - fy),
+                b: 200.0 * (fy - fz),
+            }
+        }
+    }
+
+    impl CieLab {
+        /// The CIEDE2000 color difference between 2 colors, where a difference of about
+        /// `1.0` is the smallest one that is noticeable.
+        pub fn ciede2000(&self, other: &CieLab) -> f32 {
+            // This is computed in f64, since the formula is sensitive to rounding near
+            // hue angle boundaries.
+            let (l1, a1, b1) = (self.l as f64, self.a as f64, self.b as f64);
+            let (l2, a2, b2) = (other.l as f64, other.a as f64, other.b as f64);
+
+            let c1 = (a1 * a1 + b1 * b1).sqrt();
+            let c2 = (a2 * a2 + b2 * b2).sqrt();
+            let c_mean = (c1 + c2) / 2.0;
+            let c_mean_pow7 = c_mean.powi(7);
+            let g = 0.5 * (1.0 - (c_mean_pow7 / (c_mean_pow7 + 25f64.powi(7))).sqrt());
+
+            let a1_prime = a1 * (1.0 + g);
+            let a2_prime = a2 * (1.0 + g);
+            let c1_prime = (a1_prime * a1_prime + b1 * b1).sqrt();
+            let c2_prime = (a2_prime * a2_prime + b2 * b2).sqrt();
+
+            let hue = |b: f64, a_prime: f64| {
+                if b == 0.0 && a_prime == 0.0 {
+                    0.0
+                } else {
+                    b.atan2(a_prime).to_degrees().rem_euclid(360.0)
+                }
+            };
+            let h1_prime = hue(b1, a1_prime);
+            let h2_prime = hue(b2, a2_prime);
+
+            let delta_l_prime = l2 - l1;
+            let delta_c_prime = c2_prime - c1_prime;
+            let delta_h_prime = if c1_prime * c2_prime == 0.0 {
+                0.0
+            } else if (h2_prime - h1_prime).abs() <= 180.0 {
+                h2_prime - h1_prime
+            } else if h2_prime <= h1_prime {
+                h2_prime - h1_prime + 360.0
+            } else {
+                h2_prime - h1_prime - 360.0
+            };
+            let delta_big_h_prime =
+                2.0 * (c1_prime * c2_prime).sqrt() * (delta_h_prime.to_radians() / 2.0).sin();
+
+            let l_mean_prime = (l1 + l2) / 2.0;
+            let c_mean_prime = (c1_prime + c2_prime) / 2.0;
+            let h_mean_prime = if c1_prime * c2_prime == 0.0 {
+                h1_prime + h2_prime
+            } else if (h1_prime - h2_prime).abs() <= 180.0 {
+                (h1_prime + h2_prime) / 2.0
+            } else if h1_prime + h2_prime < 360.0 {
+                (h1_prime + h2_prime + 360.0) / 2.0
+            } else {
+                (h1_prime + h2_prime - 360.0) / 2.0
+            };
+
+            let t = 1.0 - 0.17 * (h_mean_prime - 30.0).to_radians().cos()
+                + 0.24 * (2.0 * h_mean_prime).to_radians().cos()
+                + 0.32 * (3.0 * h_mean_prime + 6.0).to_radians().cos()
+                - 0.20 * (4.0 * h_mean_prime - 63.0).to_radians().cos();
+            let delta_theta = 30.0 * (-((h_mean_prime - 275.0) / 25.0).powi(2)).exp();
+            let c_mean_prime_pow7 = c_mean_prime.powi(7);
+            let r_c = 2.0 * (c_mean_prime_pow7 / (c_mean_prime_pow7 + 25f64.powi(7))).sqrt();
+            let l_offset = (l_mean_prime - 50.0).powi(2);
+            let s_l = 1.0 + (0.015 * l_offset) / (20.0 + l_offset).sqrt();
+            let s_c = 1.0 + 0.045 * c_mean_prime;
+            let s_h = 1.0 + 0.015 * c_mean_prime * t;
+            let r_t = -(2.0 * delta_theta).to_radians().sin() * r_c;
+
+            let l_term = delta_l_prime / s_l;
+            let c_term = delta_c_prime / s_c;
+            let h_term = delta_big_h_prime / s_h;
+
+            (l_term * l_term + c_term * c_term + h_term * h_term + r_t * c_term * h_term).sqrt()
+                as f32
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn test_oklab_round_trip() {
+        for rgb in [
+            0x000000, 0xffffff, 0xff0000, 0x00ff00, 0x0000ff, 0x336699, 0xafd787,
+        ] {
+            let rgb = RgbColor::from(rgb);
+            assert_eq!(RgbColor::from(Oklab::from(rgb)), rgb);
+        }
+    }
+
+    #[test]
+    fn test_oklab_reference_values() {
+        let white = Oklab::from(RgbColor::from(0xffffff));
+        assert_close(white.l, 1.0, 1e-3);
+        assert_close(white.a, 0.0, 1e-3);
+        assert_close(white.b, 0.0, 1e-3);
+
+        // Reference values from <https://bottosson.github.io/posts/oklab/>.
+        let red = Oklab::from(RgbColor::from(0xff0000));
+        assert_close(red.l, 0.628, 1e-3);
+        assert_close(red.a, 0.225, 1e-3);
+        assert_close(red.b, 0.126, 1e-3);
+    }
+
+    /// Test data from Sharma, Wu, Dalal, "The CIEDE2000 Color-Difference Formula".
+    #[test]
+    fn test_ciede2000_reference_values() {
+        let pairs = [
+            ((50.0, 2.6772, -79.7751), (50.0, 0.0, -82.7485), 2.0425),
+            ((50.0, -1.3802, -84.2814), (50.0, 0.0, -82.7485), 1.0),
+            ((50.0, 2.5, 0.0), (73.0, 25.0, -18.0), 27.1492),
+            (
+                (60.2574, -34.0099, 36.2677),
+                (60.4626, -34.1751, 39.4387),
+                1.2644,
+            ),
+            (
+                (22.7233, 20.0904, -46.694),
+                (23.0331, 14.973, -42.5619),
+                2.0373,
+            ),
+        ];
+        for ((l1, a1, b1), (l2, a2, b2), expected) in pairs {
+            let lab1 = CieLab {
+                l: l1,
+                a: a1,
+                b: b1,
+            };
+            let lab2 = CieLab {
+                l: l2,
+                a: a2,
+                b: b2,
+            };
+            assert_close(lab1.ciede2000(&lab2), expected, 1e-3);
+            assert_close(lab2.ciede2000(&lab1), expected, 1e-3);
+        }
+    }
+}
@@ -15,14 +15,53 @@
  *   limitations under the License.
  */
 
-use std::sync::atomic::{AtomicI8, Ordering};
+//! Override the [ColorSupport] that is detected at runtime by [supports_color].
+//!
+//! The color support that is used to render output is decided by the first of these
+//! that is set (highest precedence first):
+//! 1. The thread local override, see [ColorSupportOverride::scoped] and
+//!    [color_support_override_set_thread_local].
+//! 2. The global override, see [color_support_override_set].
+//! 3. The `R3BL_ANSI_COLOR` environment variable, which can be `truecolor`, `256`, `16`
+//!    or `none`.
+//! 4. The `FORCE_COLOR` environment variable, which can be `0` (no color), `1` (16
+//!    colors), `2` (256 colors) or `3` (truecolor). `FORCE_COLOR=0` turns off colors
+//!    even if `NO_COLOR` isn't set, and `FORCE_COLOR=1..3` turns on colors even if
+//!    `NO_COLOR` is set.
+//! 5. [supports_color], which checks `NO_COLOR` first, and then `CLICOLOR_FORCE` (which
+//!    forces at least 16 colors on, even when the stream isn't a TTY, or the terminal is
+//!    unknown), followed by the terminal heuristics (eg:
+//!    [KNOWN_TERMINALS](crate::KNOWN_TERMINALS)).
+//!
+//! The environment variables are only read once (the first time that they are needed).
 
-/// Global [ColorSupport] override.
-static mut COLOR_SUPPORT_OVERRIDE_VALUE: AtomicI8 =
-    AtomicI8::new(ColorSupportOverride::NotSet as i8);
+use crate::{supports_color, supports_color_in, ColorSupport, Environment, Stream};
+use std::{
+    cell::Cell,
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicI8, Ordering},
+        OnceLock,
+    },
+};
+
+/// Global [ColorSupport] override. This is the `i8` encoding of
+/// [ColorSupportOverride::NotSet] (and not its discriminant).
+static COLOR_SUPPORT_OVERRIDE_VALUE: AtomicI8 = AtomicI8::new(NOT_SET);
+
+const NOT_SET: i8 = -1;
+
+thread_local! {
+    /// Thread local [ColorSupport] override, which takes precedence over the global one.
+    static THREAD_LOCAL_COLOR_SUPPORT_OVERRIDE_VALUE: Cell<ColorSupportOverride> =
+        const { Cell::new(ColorSupportOverride::NotSet) };
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ColorSupportOverride {
+    /// Turns off colors (and all other SGR codes).
+    NoColor,
+    Ansi16,
     Ansi256,
     Truecolor,
     NotSet,
@@ -34,6 +73,8 @@ mod convert_between_color_support_override_and_i8 {
             match value {
                 1 => super::ColorSupportOverride::Ansi256,
                 2 => super::ColorSupportOverride::Truecolor,
+                3 => super::ColorSupportOverride::Ansi16,
+                4 => super::ColorSupportOverride::NoColor,
                 _ => super::ColorSupportOverride::NotSet,
             }
         }
@@ -44,18 +85,304 @@ mod convert_between_color_support_override_and_i8 {
             match value {
                 super::ColorSupportOverride::Ansi256 => 1,
                 super::ColorSupportOverride::Truecolor => 2,
-                _ => -1,
+                super::ColorSupportOverride::Ansi16 => 3,
+                super::ColorSupportOverride::NoColor => 4,
+                super::ColorSupportOverride::NotSet => super::NOT_SET,
             }
         }
     }
 }
 
+/// Sets the global (process wide) override. This is visible to all threads, unless
+/// they have their own thread local override (see [ColorSupportOverride::scoped]).
 pub fn color_support_override_set(value: ColorSupportOverride) {
-    unsafe {
-        COLOR_SUPPORT_OVERRIDE_VALUE.store(value.into(), Ordering::SeqCst);
-    };
+    COLOR_SUPPORT_OVERRIDE_VALUE.store(value.into(), Ordering::SeqCst);
+}
+
+/// Sets the override for the current thread only. Pass [ColorSupportOverride::NotSet] to
+/// fall back to the global override.
+pub fn color_support_override_set_thread_local(value: ColorSupportOverride) {
+    THREAD_LOCAL_COLOR_SUPPORT_OVERRIDE_VALUE.with(|it| it.set(value));
 }
 
+/// Returns the override for the current thread if it is set, otherwise the global one.
 pub fn color_support_override_get() -> ColorSupportOverride {
-    unsafe { COLOR_SUPPORT_OVERRIDE_VALUE.load(Ordering::SeqCst).into() }
+    match THREAD_LOCAL_COLOR_SUPPORT_OVERRIDE_VALUE.with(Cell::get) {
+        ColorSupportOverride::NotSet => COLOR_SUPPORT_OVERRIDE_VALUE.load(Ordering::SeqCst).into(),
+        value => value,
+    }
+}
+
+/// Restores the previous override when it is dropped. Returned by
+/// [ColorSupportOverride::scoped] and [ColorSupportOverride::scoped_global].
+#[must_use = "the previous override is restored as soon as the guard is dropped"]
+#[derive(Debug)]
+pub struct ColorSupportOverrideGuard {
+    previous: ColorSupportOverride,
+    is_thread_local: bool,
+    /// The thread local override must be restored on the thread that set it, so this
+    /// guard can't be sent to another thread.
+    _not_send: PhantomData<*const ()>,
+}
+
+mod color_support_override_scoped_impl {
+    use super::*;
+
+    impl ColorSupportOverride {
+        /// Sets the override for the current thread, until the returned guard is dropped.
+        /// This is the way to force a color support in tests that run in parallel.
+        ///
+        /// ```rust
+        /// use r3bl_ansi_color::*;
+        ///
+        /// {
+        ///     let _guard = ColorSupportOverride::scoped(ColorSupportOverride::Ansi256);
+        ///     assert_eq!(color_support_override_get(), ColorSupportOverride::Ansi256);
+        /// }
+        /// assert_eq!(color_support_override_get(), ColorSupportOverride::NotSet);
+        /// ```
+        pub fn scoped(value: ColorSupportOverride) -> ColorSupportOverrideGuard {
+            let previous = THREAD_LOCAL_COLOR_SUPPORT_OVERRIDE_VALUE.with(|it| it.replace(value));
+            ColorSupportOverrideGuard {
+                previous,
+                is_thread_local: true,
+                _not_send: PhantomData,
+            }
+        }
+
+        /// Sets the global override, until the returned guard is dropped. Prefer
+        /// [ColorSupportOverride::scoped] when other threads shouldn't be affected.
+        pub fn scoped_global(value: ColorSupportOverride) -> ColorSupportOverrideGuard {
+            let previous = COLOR_SUPPORT_OVERRIDE_VALUE
+                .swap(value.into(), Ordering::SeqCst)
+                .into();
+            ColorSupportOverrideGuard {
+                previous,
+                is_thread_local: false,
+                _not_send: PhantomData,
+            }
+        }
+    }
+
+    impl Drop for ColorSupportOverrideGuard {
+        fn drop(&mut self) {
+            if self.is_thread_local {
+                color_support_override_set_thread_local(self.previous);
+            } else {
+                color_support_override_set(self.previous);
+            }
+        }
+    }
+}
+
+mod color_support_override_env_impl {
+    use super::*;
+
+    impl ColorSupportOverride {
+        /// Returns the override that is set by the `R3BL_ANSI_COLOR` and `FORCE_COLOR`
+        /// environment variables (in that order), or [ColorSupportOverride::NotSet].
+        pub fn from_env() -> ColorSupportOverride {
+            Self::from_env_vars(|name| std::env::var(name).ok())
+        }
+
+        /// Same as [ColorSupportOverride::from_env], but `get_var` is used to look up
+        /// the environment variables.
+        pub fn from_env_vars(get_var: impl Fn(&str) -> Option<String>) -> ColorSupportOverride {
+            let crate_override = get_var("R3BL_ANSI_COLOR").map_or(
+                ColorSupportOverride::NotSet,
+                |value| match value.trim().to_lowercase().as_str() {
+                    "truecolor" | "24bit" => ColorSupportOverride::Truecolor,
+                    "256" | "ansi256" => ColorSupportOverride::Ansi256,
+                    "16" | "ansi16" => ColorSupportOverride::Ansi16,
+                    "none" | "nocolor" | "off" | "0" => ColorSupportOverride::NoColor,
+                    _ => ColorSupportOverride::NotSet,
+                },
+            );
+            if crate_override != ColorSupportOverride::NotSet {
+                return crate_override;
+            }
+
+            get_var("FORCE_COLOR").map_or(ColorSupportOverride::NotSet, |value| {
+                match value.trim().to_lowercase().as_str() {
+                    "0" | "false" => ColorSupportOverride::NoColor,
+                    "" | "1" | "true" => ColorSupportOverride::Ansi16,
+                    "2" => ColorSupportOverride::Ansi256,
+                    "3" => ColorSupportOverride::Truecolor,
+                    _ => ColorSupportOverride::NotSet,
+                }
+            })
+        }
+    }
+
+    /// [ColorSupportOverride::from_env], which is only read once.
+    pub fn env_color_support_override_get() -> ColorSupportOverride {
+        static ENV_COLOR_SUPPORT_OVERRIDE: OnceLock<ColorSupportOverride> = OnceLock::new();
+        *ENV_COLOR_SUPPORT_OVERRIDE.get_or_init(ColorSupportOverride::from_env)
+    }
+}
+pub use color_support_override_env_impl::*;
+
+/// Returns the [ColorSupport] to use when writing to `stream`. See the [module
+/// docs](self) for the order in which the overrides, environment variables, and runtime
+/// detection are checked.
+pub fn query_runtime_color_support(stream: Stream) -> ColorSupport {
+    resolve_color_support(
+        color_support_override_get(),
+        env_color_support_override_get(),
+        || supports_color(stream),
+    )
+}
+
+/// Same as [query_runtime_color_support], but the environment variables are read from
+/// `env` (every time), and detection runs in `env`, see [supports_color_in].
+pub fn query_runtime_color_support_in(env: &impl Environment, stream: Stream) -> ColorSupport {
+    resolve_color_support(
+        color_support_override_get(),
+        ColorSupportOverride::from_env_vars(|name| env.var(name)),
+        || supports_color_in(env, stream),
+    )
+}
+
+/// Picks the [ColorSupport] from the `code_override` (set by [ColorSupportOverride::scoped]
+/// or [color_support_override_set]), then the `env_override` (see
+/// [ColorSupportOverride::from_env]), and finally falls back to `detect`.
+pub fn resolve_color_support(
+    code_override: ColorSupportOverride,
+    env_override: ColorSupportOverride,
+    detect: impl FnOnce() -> ColorSupport,
+) -> ColorSupport {
+    let value = match code_override {
+        ColorSupportOverride::NotSet => env_override,
+        _ => code_override,
+    };
+    match value {
+        ColorSupportOverride::NotSet => detect(),
+        ColorSupportOverride::NoColor => ColorSupport::NoColor,
+        ColorSupportOverride::Ansi16 => ColorSupport::Ansi16,
+        ColorSupportOverride::Ansi256 => ColorSupport::Ansi256,
+        ColorSupportOverride::Truecolor => ColorSupport::Truecolor,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_scoped_override_is_restored() {
+        // The global override might be changed by other tests, so the thread local one
+        // is compared.
+        let thread_local_override = || THREAD_LOCAL_COLOR_SUPPORT_OVERRIDE_VALUE.with(Cell::get);
+        let before = thread_local_override();
+        {
+            let _outer = ColorSupportOverride::scoped(ColorSupportOverride::Ansi256);
+            assert_eq!(color_support_override_get(), ColorSupportOverride::Ansi256);
+            {
+                let _inner = ColorSupportOverride::scoped(ColorSupportOverride::Truecolor);
+                assert_eq!(
+                    color_support_override_get(),
+                    ColorSupportOverride::Truecolor
+                );
+                assert_eq!(
+                    query_runtime_color_support(Stream::Stdout),
+                    ColorSupport::Truecolor
+                );
+            }
+            assert_eq!(color_support_override_get(), ColorSupportOverride::Ansi256);
+        }
+        assert_eq!(thread_local_override(), before);
+    }
+
+    #[test]
+    fn test_scoped_override_is_thread_local() {
+        let _guard = ColorSupportOverride::scoped(ColorSupportOverride::Ansi16);
+        let other_thread = std::thread::spawn(color_support_override_get)
+            .join()
+            .unwrap();
+        assert_eq!(other_thread, ColorSupportOverride::NotSet);
+        assert_eq!(color_support_override_get(), ColorSupportOverride::Ansi16);
+    }
+
+    fn from_env_vars(vars: &[(&str, &str)]) -> ColorSupportOverride {
+        ColorSupportOverride::from_env_vars(|name| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn test_from_env_vars() {
+        assert_eq!(from_env_vars(&[]), ColorSupportOverride::NotSet);
+        assert_eq!(
+            from_env_vars(&[("R3BL_ANSI_COLOR", "none")]),
+            ColorSupportOverride::NoColor
+        );
+        assert_eq!(
+            from_env_vars(&[("R3BL_ANSI_COLOR", "16")]),
+            ColorSupportOverride::Ansi16
+        );
+        assert_eq!(
+            from_env_vars(&[("FORCE_COLOR", "0")]),
+            ColorSupportOverride::NoColor
+        );
+        assert_eq!(
+            from_env_vars(&[("FORCE_COLOR", "")]),
+            ColorSupportOverride::Ansi16
+        );
+        assert_eq!(
+            from_env_vars(&[("FORCE_COLOR", "2")]),
+            ColorSupportOverride::Ansi256
+        );
+        assert_eq!(
+            from_env_vars(&[("FORCE_COLOR", "3")]),
+            ColorSupportOverride::Truecolor
+        );
+        assert_eq!(
+            from_env_vars(&[("FORCE_COLOR", "banana")]),
+            ColorSupportOverride::NotSet
+        );
+        // The crate specific variable wins.
+        assert_eq!(
+            from_env_vars(&[("FORCE_COLOR", "3"), ("R3BL_ANSI_COLOR", "256")]),
+            ColorSupportOverride::Ansi256
+        );
+    }
+
+    #[test]
+    fn test_precedence_over_supports_color() {
+        // Stands in for `supports_color`, which depends on how the tests are run.
+        let detect = || ColorSupport::Ansi256;
+
+        // `FORCE_COLOR=0` turns off colors, whatever is detected.
+        let env_override = from_env_vars(&[("FORCE_COLOR", "0")]);
+        assert_eq!(
+            resolve_color_support(ColorSupportOverride::NotSet, env_override, detect),
+            ColorSupport::NoColor
+        );
+
+        // `FORCE_COLOR=3` turns on truecolor, even if the output is piped.
+        let env_override = from_env_vars(&[("FORCE_COLOR", "3")]);
+        assert_eq!(
+            resolve_color_support(ColorSupportOverride::NotSet, env_override, detect),
+            ColorSupport::Truecolor
+        );
+
+        // Overrides that are set in code win over the environment.
+        assert_eq!(
+            resolve_color_support(ColorSupportOverride::NoColor, env_override, detect),
+            ColorSupport::NoColor
+        );
+
+        // Nothing is set, so fall back to runtime detection.
+        assert_eq!(
+            resolve_color_support(
+                ColorSupportOverride::NotSet,
+                ColorSupportOverride::NotSet,
+                detect
+            ),
+            ColorSupport::Ansi256
+        );
+    }
 }
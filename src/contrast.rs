@@ -0,0 +1,281 @@
+/*
+ *   Copyright (c) 2023 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+//! Contrast between text and background colors, and picking readable text colors.
+//!
+//! The readable foreground helpers take a [ColorSupport], since eg: a background that is
+//! converted to the ANSI 256 palette might end up darker or lighter than the original.
+//! So the contrast is computed between the colors that are actually emitted.
+//!
+//! More info:
+//! - <https://www.w3.org/TR/WCAG21/#dfn-relative-luminance>
+//! - <https://www.w3.org/TR/WCAG21/#dfn-contrast-ratio>
+//! - <https://github.com/Myndex/apca-w3>
+
+use crate::*;
+
+/// The minimum contrast ratio for normal text, per WCAG 2.x level AA.
+pub const WCAG_AA_CONTRAST_RATIO: f32 = 4.5;
+
+/// The minimum contrast ratio for normal text, per WCAG 2.x level AAA.
+pub const WCAG_AAA_CONTRAST_RATIO: f32 = 7.0;
+
+/// The WCAG 2.x contrast ratio between 2 colors, which ranges from `1.0` (no contrast)
+/// to `21.0` (black on white). The order of the colors doesn't matter.
+pub fn contrast_ratio(a: impl TransformColor, b: impl TransformColor) -> f32 {
+    let (a, b) = (
+        a.as_rgb().relative_luminance(),
+        b.as_rgb().relative_luminance(),
+    );
+    let (lighter, darker) = if a > b { (a, b) } else { (b, a) };
+    (lighter + 0.05) / (darker + 0.05)
+}
+
+mod rgb_color_contrast_impl {
+    use super::*;
+
+    impl RgbColor {
+        /// The WCAG 2.x relative luminance, from `0.0` (black) to `1.0` (white).
+        pub fn relative_luminance(&self) -> f32 {
+            0.2126 * srgb_to_linear(self.red)
+                + 0.7152 * srgb_to_linear(self.green)
+                + 0.0722 * srgb_to_linear(self.blue)
+        }
+
+        /// See [contrast_ratio].
+        pub fn contrast_ratio(&self, other: impl TransformColor) -> f32 {
+            contrast_ratio(*self, other)
+        }
+
+        /// The APCA (Accessible Perceptual Contrast Algorithm) lightness contrast of
+        /// `self` as text on `background`. Unlike [contrast_ratio], the order matters: it
+        /// is positive for dark text on a light background (up to about `106`), and
+        /// negative for light text on a dark background (down to about `-108`). Values
+        /// w/ an absolute value of `60` or more are readable for body text.
+        pub fn apca_contrast(&self, background: impl TransformColor) -> f32 {
+            const BLACK_THRESHOLD: f64 = 0.022;
+            const BLACK_CLAMP: f64 = 1.414;
+            const SCALE: f64 = 1.14;
+            const OFFSET: f64 = 0.027;
+            const LOW_CLIP: f64 = 0.1;
+
+            let screen_luminance = |rgb: RgbColor| {
+                let channel = |value: u8| (value as f64 / 255.0).powf(2.4);
+                let y = 0.212_672_9 * channel(rgb.red)
+                    + 0.715_152_2 * channel(rgb.green)
+                    + 0.072_175 * channel(rgb.blue);
+                if y < BLACK_THRESHOLD {
+                    y + (BLACK_THRESHOLD - y).powf(BLACK_CLAMP)
+                } else {
+                    y
+                }
+            };
+
+            let text = screen_luminance(*self);
+            let background = screen_luminance(background.as_rgb());
+            if (background - text).abs() < 0.000_5 {
+                return 0.0;
+            }
+
+            let contrast = if background > text {
+                // Dark text on a light background.
+                let sapc = (background.powf(0.56) - text.powf(0.57)) * SCALE;
+                if sapc < LOW_CLIP {
+                    0.0
+                } else {
+                    sapc - OFFSET
+                }
+            } else {
+                // Light text on a dark background.
+                let sapc = (background.powf(0.65) - text.powf(0.62)) * SCALE;
+                if sapc > -LOW_CLIP {
+                    0.0
+                } else {
+                    sapc + OFFSET
+                }
+            };
+
+            (contrast * 100.0) as f32
+        }
+    }
+}
+
+/// Returns black or white (as emitted for `color_support`), whichever has the most
+/// contrast w/ `background` (also as emitted for `color_support`).
+pub fn readable_foreground(background: impl TransformColor, color_support: ColorSupport) -> Color {
+    let background = background_as_emitted(background, color_support);
+    let black = Color::Rgb(0, 0, 0).quantize(color_support);
+    let white = Color::Rgb(255, 255, 255).quantize(color_support);
+    if contrast_ratio(black, background) >= contrast_ratio(white, background) {
+        black
+    } else {
+        white
+    }
+}
+
+/// Returns `preferred` if it has at least `target_ratio` contrast w/ `background`, once
+/// both are quantized for `color_support`. Otherwise returns the palette color that is
+/// the closest to `preferred` (in [Oklab]) and that meets `target_ratio`. In
+/// [ColorSupport::Truecolor] mode, the candidates are lightened and darkened versions of
+/// `preferred`. If nothing meets `target_ratio`, returns [readable_foreground].
+pub fn readable_foreground_near(
+    preferred: impl TransformColor,
+    background: impl TransformColor,
+    target_ratio: f32,
+    color_support: ColorSupport,
+) -> Color {
+    let background = background_as_emitted(background, color_support);
+    let preferred_rgb = preferred.as_rgb();
+    let quantized = Color::from(preferred_rgb).quantize(color_support);
+    if contrast_ratio(quantized, background) >= target_ratio {
+        return quantized;
+    }
+
+    let candidates: Vec<Color> = match color_support {
+        ColorSupport::Ansi256 => (16..=255).map(Color::Ansi256).collect(),
+        ColorSupport::Ansi16 => (0..=15).map(Color::Ansi16).collect(),
+        ColorSupport::Truecolor | ColorSupport::NoColor => (1..=100)
+            .flat_map(|step| {
+                let amount = step as f32 / 100.0;
+                [preferred_rgb.lighten(amount), preferred_rgb.darken(amount)]
+            })
+            .map(Color::from)
+            .collect(),
+    };
+
+    let target = Oklab::from(preferred_rgb);
+    candidates
+        .into_iter()
+        .filter(|it| contrast_ratio(*it, background) >= target_ratio)
+        .min_by(|a, b| {
+            let a = target.distance_squared(&a.as_rgb().into());
+            let b = target.distance_squared(&b.as_rgb().into());
+            a.total_cmp(&b)
+        })
+        .unwrap_or_else(|| readable_foreground(background, color_support))
+}
+
+fn background_as_emitted(background: impl TransformColor, color_support: ColorSupport) -> Color {
+    Color::from(background.as_rgb()).quantize(color_support)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 0.05,
+            "{actual} is not close to {expected}"
+        );
+    }
+
+    #[test]
+    fn test_relative_luminance_and_contrast_ratio() {
+        let black = RgbColor::from(0x000000);
+        let white = RgbColor::from(0xffffff);
+        assert_close(black.relative_luminance(), 0.0);
+        assert_close(white.relative_luminance(), 1.0);
+        assert_close(contrast_ratio(black, white), 21.0);
+        assert_close(contrast_ratio(white, black), 21.0);
+        assert_close(contrast_ratio(white, white), 1.0);
+        // Reference values from the WebAIM contrast checker.
+        assert_close(RgbColor::from(0x777777).contrast_ratio(white), 4.48);
+        assert_close(RgbColor::from(0x0000ff).contrast_ratio(white), 8.59);
+    }
+
+    #[test]
+    fn test_apca_contrast() {
+        let black = RgbColor::from(0x000000);
+        let white = RgbColor::from(0xffffff);
+        assert_close(black.apca_contrast(white), 106.04);
+        assert_close(white.apca_contrast(black), -107.88);
+        // Reference value from the APCA calculator.
+        assert_close(RgbColor::from(0x888888).apca_contrast(white), 63.06);
+        assert_close(white.apca_contrast(white), 0.0);
+    }
+
+    #[test]
+    fn test_readable_foreground() {
+        assert_eq!(
+            readable_foreground(RgbColor::from(0xffff00), ColorSupport::Truecolor),
+            Color::Rgb(0, 0, 0)
+        );
+        assert_eq!(
+            readable_foreground(RgbColor::from(0x000080), ColorSupport::Truecolor),
+            Color::Rgb(255, 255, 255)
+        );
+        assert_eq!(
+            readable_foreground(RgbColor::from(0x000080), ColorSupport::Ansi256),
+            Color::Ansi256(231)
+        );
+        assert_eq!(
+            readable_foreground(Color::Ansi256(226), ColorSupport::Ansi16),
+            Color::Ansi16(0)
+        );
+    }
+
+    #[test]
+    fn test_readable_foreground_uses_the_emitted_background() {
+        // This background is in between, and quantization moves it to a lighter color.
+        let background = RgbColor::from(0x767676);
+        let in_truecolor = readable_foreground(background, ColorSupport::Truecolor);
+        let in_ansi256 = readable_foreground(background, ColorSupport::Ansi256);
+        let emitted_background = background.as_ansi256();
+        assert!(
+            contrast_ratio(in_ansi256, emitted_background)
+                >= contrast_ratio(Color::Ansi256(231), emitted_background)
+        );
+        assert_eq!(in_truecolor, Color::Rgb(0, 0, 0));
+    }
+
+    #[test]
+    fn test_readable_foreground_near() {
+        let background = RgbColor::from(0x1e1e1e);
+
+        // Already readable, so it is returned as is.
+        let preferred = RgbColor::from(0x5fafd7);
+        assert_eq!(
+            readable_foreground_near(preferred, background, 4.5, ColorSupport::Truecolor),
+            Color::from(preferred)
+        );
+
+        // Too dark, so a lighter color w/ a similar hue is picked.
+        let preferred = RgbColor::from(0x303080);
+        for color_support in [ColorSupport::Truecolor, ColorSupport::Ansi256] {
+            let color = readable_foreground_near(preferred, background, 4.5, color_support);
+            let emitted_background = Color::from(background).quantize(color_support);
+            assert!(contrast_ratio(color, emitted_background) >= 4.5);
+            let hue = OklchColor::from(color.as_rgb()).hue;
+            assert!(
+                (hue - OklchColor::from(preferred).hue).abs() < 30.0,
+                "{color:?}"
+            );
+        }
+        assert_eq!(
+            readable_foreground_near(preferred, background, 4.5, ColorSupport::Ansi256),
+            Color::Ansi256(67)
+        );
+
+        // Nothing can reach 21:1 except for black on white.
+        assert_eq!(
+            readable_foreground_near(preferred, background, 21.0, ColorSupport::Truecolor),
+            Color::Rgb(255, 255, 255)
+        );
+    }
+}
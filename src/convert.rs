@@ -19,8 +19,80 @@
 //! - <https://tintin.mudhalla.net/info/256color/>
 //! - <https://talyian.github.io/ansicolors/>
 
-use crate::{Ansi256Color, RgbColor};
-use std::cmp::Ordering::Less;
+use crate::{with_active_palette, Ansi16Color, Ansi256Color, RgbColor};
+use std::{
+    cmp::Ordering::Less,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+/// How to pick the ANSI 256 color that is closest to a [RgbColor].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Ansi256ConversionStrategy {
+    /// Uses the "redmean" metric. For the standard colors `16..=255`, this only compares
+    /// the closest color in the 6×6×6 cube and the closest gray, which is fast, but it
+    /// can snap to a visibly different hue. See [convert_rgb_into_ansi256].
+    #[default]
+    Redmean,
+    /// Searches all the colors of the active [Palette](crate::Palette) for the one w/
+    /// the smallest distance in the OKLab color space.
+    Oklab,
+    /// Searches all the colors of the active [Palette](crate::Palette) for the one w/
+    /// the smallest CIEDE2000 difference. This is the most accurate, and the slowest.
+    Ciede2000,
+}
+
+/// Global [Ansi256ConversionStrategy] that is used by
+/// [TransformColor::as_ansi256](crate::TransformColor::as_ansi256) for RGB colors.
+static ANSI256_CONVERSION_STRATEGY_VALUE: AtomicU8 = AtomicU8::new(0);
+
+mod convert_between_ansi256_conversion_strategy_and_u8 {
+    use super::Ansi256ConversionStrategy;
+
+    impl From<u8> for Ansi256ConversionStrategy {
+        fn from(value: u8) -> Self {
+            match value {
+                1 => Ansi256ConversionStrategy::Oklab,
+                2 => Ansi256ConversionStrategy::Ciede2000,
+                _ => Ansi256ConversionStrategy::Redmean,
+            }
+        }
+    }
+
+    impl From<Ansi256ConversionStrategy> for u8 {
+        fn from(value: Ansi256ConversionStrategy) -> Self {
+            match value {
+                Ansi256ConversionStrategy::Redmean => 0,
+                Ansi256ConversionStrategy::Oklab => 1,
+                Ansi256ConversionStrategy::Ciede2000 => 2,
+            }
+        }
+    }
+}
+
+pub fn ansi256_conversion_strategy_set(value: Ansi256ConversionStrategy) {
+    ANSI256_CONVERSION_STRATEGY_VALUE.store(value.into(), Ordering::SeqCst);
+}
+
+pub fn ansi256_conversion_strategy_get() -> Ansi256ConversionStrategy {
+    ANSI256_CONVERSION_STRATEGY_VALUE
+        .load(Ordering::SeqCst)
+        .into()
+}
+
+/// Converts `rgb_color` to the closest ANSI 256 color in the active
+/// [Palette](crate::Palette), using the given `strategy`.
+pub fn convert_rgb_into_ansi256_using(
+    rgb_color: RgbColor,
+    strategy: Ansi256ConversionStrategy,
+) -> Ansi256Color {
+    with_active_palette(|palette| palette.nearest_ansi256(rgb_color, strategy))
+}
+
+/// Finds the closest of the 16 system colors in the active [Palette](crate::Palette) to
+/// `rgb_color`.
+pub fn convert_rgb_into_ansi16(rgb_color: RgbColor) -> Ansi16Color {
+    with_active_palette(|palette| palette.nearest_ansi16(rgb_color))
+}
 
 pub fn convert_rgb_into_ansi256(rgb_color: RgbColor) -> Ansi256Color {
     let luminance_approximation: usize = calculate_luminance(rgb_color).into();
@@ -274,7 +346,10 @@ pub use constants::*;
 #[cfg(test)]
 mod tests {
     use crate::TransformColor;
-    use crate::{Ansi256Color, RgbColor};
+    use crate::{
+        convert_rgb_into_ansi256, convert_rgb_into_ansi256_using, Ansi16Color, Ansi256Color,
+        Ansi256ConversionStrategy, CieLab, Color, Oklab, RgbColor,
+    };
     use pretty_assertions::assert_eq;
 
     #[test]
@@ -413,4 +488,97 @@ mod tests {
             231
         );
     }
+
+    #[test]
+    fn test_convert_rgb_into_ansi16() {
+        assert_eq!(RgbColor::from(0x000000).as_ansi16().index(), 0);
+        assert_eq!(RgbColor::from(0xffffff).as_ansi16().index(), 15);
+        assert_eq!(RgbColor::from(0xc80a0a).as_ansi16().index(), 1);
+        assert_eq!(RgbColor::from(0xf00505).as_ansi16().index(), 9);
+        assert_eq!(RgbColor::from(0x1010e0).as_ansi16().index(), 4);
+        assert_eq!(RgbColor::from(0x808080).as_ansi16().index(), 8);
+    }
+
+    #[test]
+    fn test_convert_ansi256_into_ansi16() {
+        // System colors are passed through as is.
+        assert_eq!(Ansi256Color { index: 9 }.as_ansi16(), Ansi16Color::new(9));
+        // 196 is 0xff0000.
+        assert_eq!(Ansi256Color { index: 196 }.as_ansi16(), Ansi16Color::new(9));
+        // 231 is 0xffffff.
+        assert_eq!(
+            Ansi256Color { index: 231 }.as_ansi16(),
+            Ansi16Color::new(15)
+        );
+        // Out of range indices are clamped.
+        assert_eq!(Color::Ansi16(18).as_ansi16(), Ansi16Color::new(15));
+        assert_eq!(Color::Ansi16(255).as_rgb(), Ansi16Color::new(15).as_rgb());
+    }
+
+    #[test]
+    fn test_redmean_strategy_is_the_fast_path() {
+        for rgb_color in [
+            RgbColor::from(0x5fafd7),
+            RgbColor::from(0x110033),
+            RgbColor::from(0x006633),
+        ] {
+            assert_eq!(
+                convert_rgb_into_ansi256_using(rgb_color, Ansi256ConversionStrategy::Redmean),
+                convert_rgb_into_ansi256(rgb_color)
+            );
+        }
+        assert_eq!(
+            Ansi256ConversionStrategy::default(),
+            Ansi256ConversionStrategy::Redmean
+        );
+    }
+
+    #[test]
+    fn test_perceptual_strategies_on_tricky_colors() {
+        // (rgb, redmean, oklab, ciede2000)
+        #[rustfmt::skip]
+        let tricky_colors = [
+            // Very dark blue: redmean snaps to a gray.
+            (0x110033, 233, 17, 17),
+            // Dark green: redmean picks a teal.
+            (0x006633, 23, 22, 22),
+            // Medium blue: redmean picks a lighter, less saturated blue.
+            (0x003399, 24, 19, 19),
+            (0x0033ee, 27, 21, 21),
+            (0x007799, 30, 31, 31),
+        ];
+
+        for (rgb, redmean, oklab, ciede2000) in tricky_colors {
+            let rgb_color = RgbColor::from(rgb);
+            let convert = |strategy| convert_rgb_into_ansi256_using(rgb_color, strategy).index;
+            assert_eq!(convert(Ansi256ConversionStrategy::Redmean), redmean);
+            assert_eq!(convert(Ansi256ConversionStrategy::Oklab), oklab);
+            assert_eq!(convert(Ansi256ConversionStrategy::Ciede2000), ciede2000);
+
+            // The perceptual strategies are never further away than the fast path.
+            let target = Oklab::from(rgb_color);
+            let distance =
+                |index: u8| target.distance_squared(&Ansi256Color { index }.as_rgb().into());
+            assert!(distance(oklab) <= distance(redmean));
+            let target = CieLab::from(rgb_color);
+            let difference = |index: u8| target.ciede2000(&Ansi256Color { index }.as_rgb().into());
+            assert!(difference(ciede2000) < difference(redmean));
+        }
+    }
+
+    #[test]
+    fn test_perceptual_strategies_round_trip_palette_colors() {
+        for index in 16..=255 {
+            let rgb_color = Ansi256Color { index }.as_rgb();
+            for strategy in [
+                Ansi256ConversionStrategy::Oklab,
+                Ansi256ConversionStrategy::Ciede2000,
+            ] {
+                assert_eq!(
+                    convert_rgb_into_ansi256_using(rgb_color, strategy),
+                    Ansi256Color { index }
+                );
+            }
+        }
+    }
 }
@@ -15,13 +15,8 @@
  *   limitations under the License.
  */
 
-use std::env;
-
-#[test]
-fn test_supports_color() {
-    let level = supports_color(Stream::Stdout);
-    println!("🍎🍎🍎  supports_color: {:?}", level);
-}
+use crate::*;
+use std::sync::OnceLock;
 
 /// The stream to check for color support.
 #[derive(Clone, Copy, Debug)]
@@ -30,61 +25,366 @@ pub enum Stream {
     Stderr,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ColorSupport {
+    Ansi16,
     Ansi256,
     Truecolor,
     NoColor,
 }
 
+/// Detects the [ColorSupport] of `stream`, using the environment of this process. See
+/// [supports_color_in] to use some other [Environment].
 pub fn supports_color(stream: Stream) -> ColorSupport {
-    if env_no_color()
-        || as_str(&env::var("TERM")) == Ok("dumb")
-        || !(is_a_tty(stream) || env::var("IGNORE_IS_TERMINAL").map_or(false, |v| v != "0"))
+    supports_color_in(&RealEnvironment, stream)
+}
+
+/// Same as [supports_color], but the environment variables, OS, TTY and CI status are
+/// all looked up in `env`.
+pub fn supports_color_in(env: &impl Environment, stream: Stream) -> ColorSupport {
+    detect_color_support_in(env, stream).color_support
+}
+
+/// The result of color support detection, w/ the rule that decided it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColorSupportDetection {
+    pub color_support: ColorSupport,
+    pub rule: DetectionRule,
+}
+
+/// Why detection picked a [ColorSupport]. The rules are checked in the order that they
+/// are declared in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DetectionRule {
+    /// `NO_COLOR` is set (to anything other than `0`).
+    NoColorVar,
+    /// `TERM=dumb`.
+    DumbTerminal,
+    /// The stream isn't a TTY, and neither `CLICOLOR_FORCE` nor `IGNORE_IS_TERMINAL` are
+    /// set.
+    NotATty,
+    /// Inside of tmux, which reported the features of the outer terminal (see
+    /// [Environment::tmux_client_termfeatures]).
+    Tmux,
+    /// Inside of GNU screen, which doesn't support truecolor (before 5.0). `TERM` tells
+    /// whether it is set up for 256 colors.
+    GnuScreen,
+    /// A row of [KNOWN_TERMINALS] matched.
+    KnownTerminal(&'static KnownTerminal),
+    /// `COLORTERM` is `truecolor` or `24bit`.
+    ColortermTruecolor,
+    /// `COLORTERM` is set to something else.
+    ColortermSet,
+    /// The terminfo entry for `TERM` lists colors, see [Terminfo::color_support]. An
+    /// entry that only lists 8 colors doesn't override [DetectionRule::AnsiTerm].
+    Terminfo,
+    /// `TERM` is a 16 color terminal, see [check_ansi16_color].
+    Ansi16Term,
+    /// `TERM` looks like it supports colors, see [check_ansi_color].
+    AnsiTerm,
+    /// Windows consoles support colors.
+    Windows,
+    /// `CLICOLOR` is set (to anything other than `0`).
+    ClicolorVar,
+    /// Running in CI.
+    Ci,
+    /// `CLICOLOR_FORCE` is set (to anything other than `0`), so colors are on, even though
+    /// the terminal is unknown.
+    ClicolorForceVar,
+    /// None of the above.
+    Unknown,
+}
+
+/// Same as [supports_color], but also reports which [DetectionRule] matched.
+///
+/// ## Example usage:
+///
+/// ```rust
+/// use r3bl_ansi_color::*;
+///
+/// let detection = detect_color_support(Stream::Stdout);
+/// println!("{:?} ({})", detection.color_support, detection.rule);
+/// ```
+pub fn detect_color_support(stream: Stream) -> ColorSupportDetection {
+    detect_color_support_in(&RealEnvironment, stream)
+}
+
+/// Same as [detect_color_support()], but everything is looked up in `env`.
+pub fn detect_color_support_in(env: &impl Environment, stream: Stream) -> ColorSupportDetection {
+    use ColorSupport::*;
+    use DetectionRule::*;
+
+    let detected = |color_support, rule| ColorSupportDetection {
+        color_support,
+        rule,
+    };
+
+    let term = env.var("TERM");
+    let colorterm = env.var("COLORTERM");
+
+    if no_color_in(env) {
+        return detected(NoColor, NoColorVar);
+    }
+    if term.as_deref() == Some("dumb") {
+        return detected(NoColor, DumbTerminal);
+    }
+    if !(env.is_tty(stream)
+        || env.var("IGNORE_IS_TERMINAL").is_some_and(|v| v != "0")
+        || clicolor_force_in(env))
     {
-        return ColorSupport::NoColor;
+        return detected(NoColor, NotATty);
     }
 
-    if env::consts::OS == "macos" {
-        if as_str(&env::var("TERM_PROGRAM")) == Ok("Apple_Terminal")
-            && env::var("TERM").map(|term| check_256_color(&term)) == Ok(true)
-        {
-            return ColorSupport::Ansi256;
+    // The multiplexer's own `TERM` (eg: `screen-256color`) says nothing about the outer
+    // terminal. The variables that the outer terminal set (eg: `KITTY_WINDOW_ID`) might
+    // still be around though, so the rules below are used when tmux can't be asked.
+    match Multiplexer::detect_in(env) {
+        Some(Multiplexer::Tmux) => {
+            if let Some(features) = env.tmux_client_termfeatures() {
+                let has = |feature| features.split(',').any(|it| it.trim() == feature);
+                let color_support = if has("RGB") {
+                    Truecolor
+                } else if has("256") {
+                    Ansi256
+                } else {
+                    Ansi16
+                };
+                return detected(color_support, Tmux);
+            }
         }
-
-        if as_str(&env::var("TERM_PROGRAM")) == Ok("iTerm.app")
-            || as_str(&env::var("COLORTERM")) == Ok("truecolor")
-        {
-            return ColorSupport::Truecolor;
+        Some(Multiplexer::Screen) => {
+            let color_support = match term.as_deref() {
+                Some(term) if check_256_color(term) => Ansi256,
+                _ => Ansi16,
+            };
+            return detected(color_support, GnuScreen);
         }
+        None => {}
     }
 
-    if env::consts::OS == "linux" && as_str(&env::var("COLORTERM")) == Ok("truecolor") {
-        return ColorSupport::Truecolor;
+    if let Some(known_terminal) = KNOWN_TERMINALS.iter().find(|it| it.matcher.matches(env)) {
+        return detected(known_terminal.color_support, KnownTerminal(known_terminal));
     }
 
-    if env::var("COLORTERM").is_ok()
-        || env::var("TERM").map(|term| check_ansi_color(&term)) == Ok(true)
-        || env::consts::OS == "windows"
-        || env::var("CLICOLOR").map_or(false, |v| v != "0")
-        || is_ci::uncached()
-    {
-        return ColorSupport::Ansi256;
+    if matches!(colorterm.as_deref(), Some("truecolor" | "24bit")) {
+        return detected(Truecolor, ColortermTruecolor);
+    }
+
+    if colorterm.is_some() {
+        return detected(Ansi256, ColortermSet);
+    }
+
+    // When the entry doesn't list any colors, the heuristics below get to decide, since
+    // eg: `vt100` is often used for serial consoles that do support colors. The entry
+    // can't downgrade `TERM=xterm` either, which says `colors#8`, even though pretty much
+    // every terminal that uses it supports 256 colors.
+    if let Some(color_support) = env.terminfo().and_then(|it| it.color_support()) {
+        let is_ansi_term = term
+            .as_deref()
+            .is_some_and(|it| !check_ansi16_color(it) && check_ansi_color(it));
+        if !(color_support == Ansi16 && is_ansi_term) {
+            return detected(color_support, DetectionRule::Terminfo);
+        }
+    }
+
+    if term.as_deref().is_some_and(check_ansi16_color) {
+        return detected(Ansi16, Ansi16Term);
+    }
+    if term.as_deref().is_some_and(check_ansi_color) {
+        return detected(Ansi256, AnsiTerm);
     }
+    if env.os() == "windows" {
+        return detected(Ansi256, Windows);
+    }
+    if env.var("CLICOLOR").is_some_and(|v| v != "0") {
+        return detected(Ansi256, ClicolorVar);
+    }
+    if env.is_ci() {
+        return detected(Ansi256, Ci);
+    }
+    if clicolor_force_in(env) {
+        return detected(Ansi16, ClicolorForceVar);
+    }
+
+    detected(NoColor, Unknown)
+}
+
+/// A terminal that can be recognized by its environment variables, and the colors that
+/// it supports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KnownTerminal {
+    pub name: &'static str,
+    pub matcher: TerminalMatcher,
+    pub color_support: ColorSupport,
+}
 
-    ColorSupport::NoColor
+/// Matches an environment variable, see [KnownTerminal].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TerminalMatcher {
+    /// The variable is set to this value.
+    VarEquals(&'static str, &'static str),
+    /// The value of the variable starts w/ this prefix.
+    VarStartsWith(&'static str, &'static str),
+    /// The variable is set (to anything).
+    VarIsSet(&'static str),
+    /// The variable is a number that is at least this.
+    VarAtLeast(&'static str, u32),
+    /// The value of the variable contains this.
+    VarContains(&'static str, &'static str),
+    /// The operating system is this, see [Environment::os].
+    OsIs(&'static str),
+    /// All of these match.
+    All(&'static [TerminalMatcher]),
 }
 
-fn is_a_tty(stream: Stream) -> bool {
-    use is_terminal::*;
-    match stream {
-        Stream::Stdout => std::io::stdout().is_terminal(),
-        Stream::Stderr => std::io::stderr().is_terminal(),
+/// The terminals that are recognized by detection, in the order that they are checked.
+/// Terminals that have more than one way to be recognized have more than one row.
+///
+/// These are checked before `COLORTERM` and the terminfo entry, since they are more
+/// specific. The downside is that variables like `KITTY_WINDOW_ID`, `WT_SESSION`,
+/// `KONSOLE_VERSION` or `VTE_VERSION` are inherited by every process that is started from
+/// that terminal, eg: by tmux (when it can't be asked about the outer terminal), or by a
+/// shell that is started w/ a different `TERM`. In those nested sessions, the outer
+/// terminal's colors are reported. GNU screen is checked first, see
+/// [DetectionRule::GnuScreen].
+///
+/// More info:
+/// - <https://github.com/termstandard/colors>
+/// - <https://sw.kovidgoyal.net/kitty/glossary/#envvar-KITTY_WINDOW_ID>
+/// - <https://wezfurlong.org/wezterm/config/lua/config/term.html>
+#[rustfmt::skip]
+pub const KNOWN_TERMINALS: &[KnownTerminal] = {
+    use ColorSupport::*;
+    use TerminalMatcher::*;
+    &[
+        // Only w/ a 256 color `TERM`, otherwise the `TERM` rules decide.
+        KnownTerminal::new("Apple Terminal",   All(&[
+                                                   OsIs("macos"),
+                                                   VarEquals("TERM_PROGRAM", "Apple_Terminal"),
+                                                   VarContains("TERM", "256"),
+                                               ]),                                          Ansi256),
+        KnownTerminal::new("iTerm2",           VarEquals("TERM_PROGRAM", "iTerm.app"),      Truecolor),
+        KnownTerminal::new("kitty",            VarEquals("TERM", "xterm-kitty"),            Truecolor),
+        KnownTerminal::new("kitty",            VarIsSet("KITTY_WINDOW_ID"),                 Truecolor),
+        KnownTerminal::new("WezTerm",          VarEquals("TERM_PROGRAM", "WezTerm"),        Truecolor),
+        KnownTerminal::new("WezTerm",          VarEquals("TERM", "wezterm"),                Truecolor),
+        KnownTerminal::new("Alacritty",        VarEquals("TERM", "alacritty"),              Truecolor),
+        KnownTerminal::new("Alacritty",        VarIsSet("ALACRITTY_WINDOW_ID"),             Truecolor),
+        KnownTerminal::new("Alacritty",        VarIsSet("ALACRITTY_SOCKET"),                Truecolor),
+        KnownTerminal::new("Alacritty",        VarIsSet("ALACRITTY_LOG"),                   Truecolor),
+        KnownTerminal::new("VS Code",          VarEquals("TERM_PROGRAM", "vscode"),         Truecolor),
+        KnownTerminal::new("Ghostty",          VarEquals("TERM_PROGRAM", "ghostty"),        Truecolor),
+        KnownTerminal::new("Ghostty",          VarEquals("TERM", "xterm-ghostty"),          Truecolor),
+        KnownTerminal::new("foot",             VarStartsWith("TERM", "foot"),               Truecolor),
+        KnownTerminal::new("Konsole",          VarIsSet("KONSOLE_VERSION"),                 Truecolor),
+        KnownTerminal::new("Windows Terminal", VarIsSet("WT_SESSION"),                      Truecolor),
+        KnownTerminal::new("Hyper",            VarEquals("TERM_PROGRAM", "Hyper"),          Truecolor),
+        KnownTerminal::new("mintty",           VarEquals("TERM_PROGRAM", "mintty"),         Truecolor),
+        // VTE based terminals (eg: GNOME Terminal, Tilix) support truecolor since 0.36.
+        KnownTerminal::new("VTE",              VarAtLeast("VTE_VERSION", 3600),             Truecolor),
+    ]
+};
+
+mod known_terminal_impl {
+    use super::*;
+
+    impl KnownTerminal {
+        pub const fn new(
+            name: &'static str,
+            matcher: TerminalMatcher,
+            color_support: ColorSupport,
+        ) -> Self {
+            Self {
+                name,
+                matcher,
+                color_support,
+            }
+        }
+    }
+
+    impl TerminalMatcher {
+        pub fn matches(&self, env: &impl Environment) -> bool {
+            match *self {
+                TerminalMatcher::VarEquals(name, value) => env.var(name).as_deref() == Some(value),
+                TerminalMatcher::VarStartsWith(name, prefix) => {
+                    env.var(name).is_some_and(|it| it.starts_with(prefix))
+                }
+                TerminalMatcher::VarIsSet(name) => env.var(name).is_some(),
+                TerminalMatcher::VarAtLeast(name, min) => env
+                    .var(name)
+                    .and_then(|it| it.trim().parse::<u32>().ok())
+                    .is_some_and(|it| it >= min),
+                TerminalMatcher::VarContains(name, needle) => {
+                    env.var(name).is_some_and(|it| it.contains(needle))
+                }
+                TerminalMatcher::OsIs(os) => env.os() == os,
+                TerminalMatcher::All(matchers) => matchers.iter().all(|it| it.matches(env)),
+            }
+        }
+    }
+
+    impl std::fmt::Display for TerminalMatcher {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                TerminalMatcher::VarEquals(name, value) => write!(f, "{name}={value}"),
+                TerminalMatcher::VarStartsWith(name, prefix) => write!(f, "{name}={prefix}*"),
+                TerminalMatcher::VarIsSet(name) => write!(f, "{name} is set"),
+                TerminalMatcher::VarAtLeast(name, min) => write!(f, "{name}>={min}"),
+                TerminalMatcher::VarContains(name, needle) => write!(f, "{name}=*{needle}*"),
+                TerminalMatcher::OsIs(os) => write!(f, "OS is {os}"),
+                TerminalMatcher::All(matchers) => {
+                    for (index, matcher) in matchers.iter().enumerate() {
+                        if index > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "{matcher}")?;
+                    }
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    impl std::fmt::Display for DetectionRule {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                DetectionRule::NoColorVar => write!(f, "NO_COLOR is set"),
+                DetectionRule::DumbTerminal => write!(f, "TERM=dumb"),
+                DetectionRule::NotATty => write!(f, "not a TTY"),
+                DetectionRule::Tmux => write!(f, "tmux client features"),
+                DetectionRule::GnuScreen => write!(f, "GNU screen"),
+                DetectionRule::KnownTerminal(known_terminal) => {
+                    write!(f, "{} ({})", known_terminal.name, known_terminal.matcher)
+                }
+                DetectionRule::ColortermTruecolor => write!(f, "COLORTERM is truecolor or 24bit"),
+                DetectionRule::ColortermSet => write!(f, "COLORTERM is set"),
+                DetectionRule::Terminfo => write!(f, "terminfo entry for TERM"),
+                DetectionRule::Ansi16Term => write!(f, "TERM is a 16 color terminal"),
+                DetectionRule::AnsiTerm => write!(f, "TERM supports colors"),
+                DetectionRule::Windows => write!(f, "Windows"),
+                DetectionRule::ClicolorVar => write!(f, "CLICOLOR is set"),
+                DetectionRule::Ci => write!(f, "CI"),
+                DetectionRule::ClicolorForceVar => write!(f, "CLICOLOR_FORCE is set"),
+                DetectionRule::Unknown => write!(f, "unknown terminal"),
+            }
+        }
     }
 }
 
 fn check_256_color(term: &str) -> bool {
-    term.ends_with("256") || term.ends_with("256color")
+    term.contains("256")
+}
+
+/// Terminals that only understand the 16 system colors (SGR 30–37, 90–97, 40–47,
+/// 100–107), eg: the Linux virtual console, and serial consoles.
+pub fn check_ansi16_color(term: &str) -> bool {
+    term == "linux"
+        || term == "ansi"
+        || term == "cons25"
+        || term.starts_with("vt100")
+        || term.starts_with("vt102")
+        || term.starts_with("vt220")
+        || term.ends_with("16color")
 }
 
 pub fn check_ansi_color(term: &str) -> bool {
@@ -99,16 +399,446 @@ pub fn check_ansi_color(term: &str) -> bool {
         || term.contains("linux")
 }
 
+/// `CLICOLOR_FORCE` (set to anything other than `0`) turns on colors, even when the
+/// stream isn't a TTY, or the terminal is unknown (at least 16 colors). More info:
+/// <https://bixense.com/clicolors/>.
+pub fn env_clicolor_force() -> bool {
+    clicolor_force_in(&RealEnvironment)
+}
+
+fn clicolor_force_in(env: &impl Environment) -> bool {
+    env.var("CLICOLOR_FORCE")
+        .is_some_and(|v| !v.is_empty() && v != "0")
+}
+
 pub fn env_no_color() -> bool {
-    match as_str(&env::var("NO_COLOR")) {
-        Ok("0") | Err(_) => false,
-        Ok(_) => true,
+    no_color_in(&RealEnvironment)
+}
+
+fn no_color_in(env: &impl Environment) -> bool {
+    env.var("NO_COLOR").is_some_and(|v| v != "0")
+}
+
+/// Whether the terminal's background is dark or light, and its color when it is known
+/// (eg: from an `OSC 11` reply).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BackgroundKind {
+    Dark(Option<RgbColor>),
+    Light(Option<RgbColor>),
+}
+
+impl BackgroundKind {
+    /// Light if black text has more contrast on `rgb` than white text.
+    pub fn from_rgb(rgb: RgbColor) -> Self {
+        if contrast_ratio(rgb, RgbColor::from(0x000000))
+            > contrast_ratio(rgb, RgbColor::from(0xffffff))
+        {
+            BackgroundKind::Light(Some(rgb))
+        } else {
+            BackgroundKind::Dark(Some(rgb))
+        }
+    }
+
+    pub fn is_dark(&self) -> bool {
+        matches!(self, BackgroundKind::Dark(_))
+    }
+
+    pub fn rgb(&self) -> Option<RgbColor> {
+        match self {
+            BackgroundKind::Dark(rgb) | BackgroundKind::Light(rgb) => *rgb,
+        }
+    }
+}
+
+/// Detects whether the terminal's background is dark or light. The result is cached,
+/// since the terminal might have to be queried. See [detect_background_in] for how it
+/// works. W/ the `terminal-query` feature (on Unix), the terminal is asked for its
+/// background color (`OSC 11`) first.
+pub fn detect_background() -> Option<BackgroundKind> {
+    static BACKGROUND: OnceLock<Option<BackgroundKind>> = OnceLock::new();
+    *BACKGROUND.get_or_init(|| detect_background_in(&RealEnvironment, query_background_color))
+}
+
+#[cfg(all(unix, feature = "terminal-query"))]
+fn query_background_color() -> Option<RgbColor> {
+    use is_terminal::IsTerminal;
+    if !std::io::stdout().is_terminal() {
+        return None;
     }
+    query_tty_colors([], std::time::Duration::from_millis(100))
+        .ok()?
+        .background
+}
+
+#[cfg(not(all(unix, feature = "terminal-query")))]
+fn query_background_color() -> Option<RgbColor> {
+    None
 }
 
-fn as_str<E>(option: &Result<String, E>) -> Result<&str, &E> {
-    match option {
-        Ok(inner) => Ok(inner),
-        Err(e) => Err(e),
+/// Same as [detect_background], w/out caching, where the environment variables are
+/// looked up in `env`, and `query_background_color` asks the terminal for its background
+/// color. These are checked in order:
+/// 1. The color that the terminal reports.
+/// 2. `COLORFGBG` (eg: `15;0`), set by rxvt, Konsole and others, where the last field is
+///    the palette index of the background.
+/// 3. The defaults of well known terminals, eg: the Linux console is dark.
+pub fn detect_background_in(
+    env: &impl Environment,
+    query_background_color: impl FnOnce() -> Option<RgbColor>,
+) -> Option<BackgroundKind> {
+    if let Some(rgb) = query_background_color() {
+        return Some(BackgroundKind::from_rgb(rgb));
+    }
+
+    if let Some(colorfgbg) = env.var("COLORFGBG") {
+        // The background is the last field, eg: `15;default;0`.
+        match colorfgbg.rsplit(';').next().map(str::parse::<u8>) {
+            Some(Ok(0..=6 | 8)) => return Some(BackgroundKind::Dark(None)),
+            Some(Ok(7 | 9..=15)) => return Some(BackgroundKind::Light(None)),
+            _ => {}
+        }
+    }
+
+    match env.var("TERM_PROGRAM").as_deref() {
+        // The default profile ("Basic") has a white background.
+        Some("Apple_Terminal") => return Some(BackgroundKind::Light(None)),
+        Some("vscode") => return Some(BackgroundKind::Dark(None)),
+        _ => {}
+    }
+    if env.var("WT_SESSION").is_some() || env.var("TERM").as_deref() == Some("linux") {
+        return Some(BackgroundKind::Dark(None));
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn fake_env(vars: &[(&str, &str)]) -> FakeEnvironment {
+        vars.iter()
+            .fold(FakeEnvironment::new(), |env, (name, value)| {
+                env.var(name, value)
+            })
+    }
+
+    #[rustfmt::skip]
+    #[test]
+    fn test_supports_color_matrix() {
+        use ColorSupport::*;
+
+        // (os, is_tty, is_ci, vars, expected)
+        type Row = (&'static str, bool, bool, &'static [(&'static str, &'static str)], ColorSupport);
+        let matrix: &[Row] = &[
+            // Apple Terminal only supports 256 colors.
+            ("macos", true, false, &[("TERM_PROGRAM", "Apple_Terminal"), ("TERM", "xterm-256color")], Ansi256),
+            ("macos", true, false, &[("TERM_PROGRAM", "Apple_Terminal"), ("TERM", "xterm")], Ansi256),
+            ("macos", true, false, &[("TERM_PROGRAM", "Apple_Terminal"), ("TERM", "vt100")], Ansi16),
+            // The Apple Terminal row only applies on macOS.
+            ("linux", true, false, &[("TERM_PROGRAM", "Apple_Terminal"), ("COLORTERM", "truecolor")], Truecolor),
+            ("macos", true, false, &[("TERM_PROGRAM", "iTerm.app"), ("TERM", "xterm-256color")], Truecolor),
+            ("macos", true, false, &[("COLORTERM", "truecolor")], Truecolor),
+            ("linux", true, false, &[("COLORTERM", "truecolor"), ("TERM", "xterm-256color")], Truecolor),
+            ("linux", true, false, &[("COLORTERM", "1"), ("TERM", "xterm")], Ansi256),
+            ("linux", true, false, &[("COLORTERM", "24bit"), ("TERM", "xterm-256color")], Truecolor),
+            ("windows", true, false, &[("COLORTERM", "truecolor")], Truecolor),
+            // Known terminals, on any OS (eg: over SSH).
+            ("linux", true, false, &[("TERM_PROGRAM", "iTerm.app")], Truecolor),
+            ("linux", true, false, &[("TERM", "xterm-kitty")], Truecolor),
+            ("linux", true, false, &[("KITTY_WINDOW_ID", "1"), ("TERM", "xterm-256color")], Truecolor),
+            ("macos", true, false, &[("TERM_PROGRAM", "WezTerm"), ("TERM", "xterm-256color")], Truecolor),
+            ("linux", true, false, &[("TERM", "alacritty")], Truecolor),
+            ("linux", true, false, &[("ALACRITTY_SOCKET", "/run/user/1000/Alacritty.sock")], Truecolor),
+            ("linux", true, false, &[("TERM_PROGRAM", "vscode"), ("TERM", "xterm-256color")], Truecolor),
+            ("macos", true, false, &[("TERM", "xterm-ghostty")], Truecolor),
+            ("linux", true, false, &[("TERM", "foot-extra")], Truecolor),
+            ("linux", true, false, &[("KONSOLE_VERSION", "230804"), ("TERM", "xterm-256color")], Truecolor),
+            ("windows", true, false, &[("WT_SESSION", "c5a4b0e2")], Truecolor),
+            ("linux", true, false, &[("VTE_VERSION", "7600"), ("TERM", "xterm-256color")], Truecolor),
+            ("linux", true, false, &[("VTE_VERSION", "3405"), ("TERM", "xterm-256color")], Ansi256),
+            ("linux", true, false, &[("TERM", "xterm-256color")], Ansi256),
+            ("linux", true, false, &[("TERM", "linux")], Ansi16),
+            ("linux", true, false, &[("TERM", "linux"), ("COLORTERM", "1")], Ansi256),
+            ("windows", true, false, &[], Ansi256),
+            ("linux", true, false, &[], NoColor),
+            // TERM=dumb and NO_COLOR win over everything else.
+            ("linux", true, false, &[("TERM", "dumb"), ("COLORTERM", "truecolor")], NoColor),
+            ("linux", true, true, &[("TERM", "dumb"), ("CLICOLOR_FORCE", "1")], NoColor),
+            ("linux", true, false, &[("NO_COLOR", "1"), ("COLORTERM", "truecolor")], NoColor),
+            ("macos", true, false, &[("NO_COLOR", ""), ("TERM_PROGRAM", "iTerm.app")], NoColor),
+            ("linux", true, false, &[("NO_COLOR", "0"), ("COLORTERM", "truecolor")], Truecolor),
+            // Not a TTY, unless CLICOLOR_FORCE or IGNORE_IS_TERMINAL says otherwise.
+            ("linux", false, false, &[("COLORTERM", "truecolor")], NoColor),
+            ("linux", false, false, &[("COLORTERM", "truecolor"), ("CLICOLOR_FORCE", "1")], Truecolor),
+            ("linux", false, false, &[("COLORTERM", "truecolor"), ("CLICOLOR_FORCE", "0")], NoColor),
+            ("linux", false, false, &[("TERM", "xterm"), ("IGNORE_IS_TERMINAL", "1")], Ansi256),
+            // CLICOLOR and CI turn on 256 colors for unknown terminals.
+            ("linux", true, false, &[("CLICOLOR", "1")], Ansi256),
+            ("linux", true, false, &[("CLICOLOR", "0")], NoColor),
+            ("linux", true, true, &[], Ansi256),
+            ("linux", false, true, &[], NoColor),
+            ("linux", false, true, &[("CLICOLOR_FORCE", "1")], Ansi256),
+            // CLICOLOR_FORCE turns on at least 16 colors, even for unknown terminals.
+            ("linux", false, false, &[("CLICOLOR_FORCE", "1")], Ansi16),
+            ("linux", true, false, &[("TERM", "r3bl-term"), ("CLICOLOR_FORCE", "1")], Ansi16),
+            ("linux", true, false, &[("CLICOLOR_FORCE", "0")], NoColor),
+            // Nested sessions inherit the outer terminal's variables. GNU screen is
+            // checked first, but otherwise the outer terminal wins (see KNOWN_TERMINALS).
+            ("linux", true, false, &[("KITTY_WINDOW_ID", "1"), ("TERM", "screen"), ("STY", "1234.pts-0.host")], Ansi16),
+            ("linux", true, false, &[("KITTY_WINDOW_ID", "1"), ("TERM", "screen-256color"), ("STY", "1234.pts-0.host")], Ansi256),
+            ("linux", true, false, &[("KITTY_WINDOW_ID", "1"), ("TERM", "screen")], Truecolor),
+            ("linux", true, false, &[("KITTY_WINDOW_ID", "1"), ("TERM", "tmux-256color"), ("TMUX", "/tmp/tmux-1000/default,1,0")], Truecolor),
+            ("linux", true, false, &[("WT_SESSION", "c5a4b0e2"), ("TERM", "vt100")], Truecolor),
+        ];
+
+        for (os, is_tty, is_ci, vars, expected) in matrix {
+            let mut env = fake_env(vars).os(os).ci(*is_ci);
+            env.stdout_is_tty = *is_tty;
+            assert_eq!(
+                supports_color_in(&env, Stream::Stdout),
+                *expected,
+                "os: {os}, is_tty: {is_tty}, is_ci: {is_ci}, vars: {vars:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_detection_reports_the_rule() {
+        let env = fake_env(&[("TERM", "xterm-kitty")]).tty(Stream::Stdout);
+        let detection = detect_color_support_in(&env, Stream::Stdout);
+        assert_eq!(detection.color_support, ColorSupport::Truecolor);
+        assert_eq!(detection.rule.to_string(), "kitty (TERM=xterm-kitty)");
+
+        let env = fake_env(&[("VTE_VERSION", "6003")]).tty(Stream::Stdout);
+        assert_eq!(
+            detect_color_support_in(&env, Stream::Stdout)
+                .rule
+                .to_string(),
+            "VTE (VTE_VERSION>=3600)"
+        );
+
+        let env = fake_env(&[
+            ("TERM_PROGRAM", "Apple_Terminal"),
+            ("TERM", "xterm-256color"),
+        ])
+        .os("macos")
+        .tty(Stream::Stdout);
+        assert_eq!(
+            detect_color_support_in(&env, Stream::Stdout)
+                .rule
+                .to_string(),
+            "Apple Terminal (OS is macos, TERM_PROGRAM=Apple_Terminal, TERM=*256*)"
+        );
+
+        let env = fake_env(&[("TERM", "xterm"), ("NO_COLOR", "1")]).tty(Stream::Stdout);
+        assert_eq!(
+            detect_color_support_in(&env, Stream::Stdout).rule,
+            DetectionRule::NoColorVar
+        );
+
+        let env = fake_env(&[("TERM", "xterm")]).tty(Stream::Stdout);
+        assert_eq!(
+            detect_color_support_in(&env, Stream::Stdout),
+            ColorSupportDetection {
+                color_support: ColorSupport::Ansi256,
+                rule: DetectionRule::AnsiTerm,
+            }
+        );
+    }
+
+    #[test]
+    fn test_supports_color_uses_terminfo() {
+        let terminfo = |bytes: &[u8]| Terminfo::parse(bytes).unwrap();
+        let xterm = terminfo(include_bytes!("../tests/fixtures/terminfo/x/xterm"));
+        let xterm_direct = terminfo(include_bytes!("../tests/fixtures/terminfo/x/xterm-direct"));
+        let vt100 = terminfo(include_bytes!("../tests/fixtures/terminfo/v/vt100"));
+        let r3bl_truecolor = terminfo(include_bytes!(
+            "../tests/fixtures/terminfo/r/r3bl-truecolor"
+        ));
+
+        let detect = |term: &str, terminfo: &Terminfo| {
+            let env = fake_env(&[("TERM", term)])
+                .terminfo(terminfo.clone())
+                .tty(Stream::Stdout);
+            detect_color_support_in(&env, Stream::Stdout)
+        };
+        let by_terminfo = |color_support| ColorSupportDetection {
+            color_support,
+            rule: DetectionRule::Terminfo,
+        };
+
+        // The entry says `colors#8`, which doesn't downgrade what `TERM` says.
+        assert_eq!(
+            detect("xterm", &xterm),
+            ColorSupportDetection {
+                color_support: ColorSupport::Ansi256,
+                rule: DetectionRule::AnsiTerm,
+            }
+        );
+        // But the entry is used when `TERM` isn't recognized.
+        assert_eq!(
+            detect("r3bl-term", &xterm),
+            by_terminfo(ColorSupport::Ansi16)
+        );
+        assert_eq!(
+            detect("xterm-direct", &xterm_direct),
+            by_terminfo(ColorSupport::Truecolor)
+        );
+        assert_eq!(
+            detect("r3bl-truecolor", &r3bl_truecolor),
+            by_terminfo(ColorSupport::Truecolor)
+        );
+        // No colors in the entry, so the heuristics decide.
+        assert_eq!(
+            detect("vt100", &vt100),
+            ColorSupportDetection {
+                color_support: ColorSupport::Ansi16,
+                rule: DetectionRule::Ansi16Term,
+            }
+        );
+        // COLORTERM wins over the entry.
+        let env = fake_env(&[("TERM", "xterm"), ("COLORTERM", "24bit")])
+            .terminfo(xterm)
+            .tty(Stream::Stdout);
+        assert_eq!(
+            supports_color_in(&env, Stream::Stdout),
+            ColorSupport::Truecolor
+        );
+    }
+
+    #[test]
+    fn test_supports_color_in_tmux_and_screen() {
+        let tmux = fake_env(&[
+            ("TMUX", "/tmp/tmux-1000/default,1234,0"),
+            ("TERM", "screen-256color"),
+            ("KITTY_WINDOW_ID", "1"),
+        ])
+        .tty(Stream::Stdout);
+        let detect = |env: &FakeEnvironment| detect_color_support_in(env, Stream::Stdout);
+
+        assert_eq!(
+            detect(
+                &tmux
+                    .clone()
+                    .tmux_client_termfeatures("256,RGB,bpaste,title")
+            ),
+            ColorSupportDetection {
+                color_support: ColorSupport::Truecolor,
+                rule: DetectionRule::Tmux,
+            }
+        );
+        assert_eq!(
+            detect(&tmux.clone().tmux_client_termfeatures("256,bpaste,title")).color_support,
+            ColorSupport::Ansi256
+        );
+        assert_eq!(
+            detect(&tmux.clone().tmux_client_termfeatures("title")).color_support,
+            ColorSupport::Ansi16
+        );
+        // tmux can't be asked, so the variables of the outer terminal are used.
+        assert_eq!(
+            detect(&tmux).rule.to_string(),
+            "kitty (KITTY_WINDOW_ID is set)"
+        );
+
+        let screen = |term: &str| {
+            fake_env(&[
+                ("STY", "1234.pts-0.host"),
+                ("TERM", term),
+                ("COLORTERM", "truecolor"),
+            ])
+            .tty(Stream::Stdout)
+        };
+        assert_eq!(
+            detect(&screen("screen.xterm-256color")),
+            ColorSupportDetection {
+                color_support: ColorSupport::Ansi256,
+                rule: DetectionRule::GnuScreen,
+            }
+        );
+        assert_eq!(
+            detect(&screen("screen")).color_support,
+            ColorSupport::Ansi16
+        );
+    }
+
+    #[test]
+    fn test_supports_color_checks_the_given_stream() {
+        let env = fake_env(&[("COLORTERM", "truecolor")]).tty(Stream::Stderr);
+        assert_eq!(
+            supports_color_in(&env, Stream::Stderr),
+            ColorSupport::Truecolor
+        );
+        assert_eq!(
+            supports_color_in(&env, Stream::Stdout),
+            ColorSupport::NoColor
+        );
+    }
+
+    fn detect(vars: &[(&str, &str)], background_color: Option<RgbColor>) -> Option<BackgroundKind> {
+        detect_background_in(&fake_env(vars), || background_color)
+    }
+
+    #[test]
+    fn test_background_from_terminal_reply() {
+        let dark = RgbColor::from(0x1e1e1e);
+        let light = RgbColor::from(0xfdf6e3);
+        assert_eq!(
+            detect(&[], Some(dark)),
+            Some(BackgroundKind::Dark(Some(dark)))
+        );
+        assert_eq!(
+            detect(&[], Some(light)),
+            Some(BackgroundKind::Light(Some(light)))
+        );
+        // The reply wins over the environment.
+        assert_eq!(
+            detect(&[("COLORFGBG", "0;15")], Some(dark)),
+            Some(BackgroundKind::Dark(Some(dark)))
+        );
+    }
+
+    #[test]
+    fn test_background_from_colorfgbg() {
+        assert_eq!(
+            detect(&[("COLORFGBG", "15;0")], None),
+            Some(BackgroundKind::Dark(None))
+        );
+        assert_eq!(
+            detect(&[("COLORFGBG", "0;15")], None),
+            Some(BackgroundKind::Light(None))
+        );
+        assert_eq!(
+            detect(&[("COLORFGBG", "15;default;8")], None),
+            Some(BackgroundKind::Dark(None))
+        );
+        assert_eq!(detect(&[("COLORFGBG", "0;default")], None), None);
+    }
+
+    #[test]
+    fn test_background_from_terminal_program() {
+        assert_eq!(
+            detect(&[("TERM_PROGRAM", "Apple_Terminal")], None),
+            Some(BackgroundKind::Light(None))
+        );
+        assert_eq!(
+            detect(&[("WT_SESSION", "1")], None),
+            Some(BackgroundKind::Dark(None))
+        );
+        assert_eq!(
+            detect(&[("TERM", "linux")], None),
+            Some(BackgroundKind::Dark(None))
+        );
+        assert_eq!(detect(&[("TERM", "xterm-256color")], None), None);
+    }
+
+    #[test]
+    fn test_background_kind() {
+        let kind = BackgroundKind::from_rgb(RgbColor::from(0x808080));
+        assert!(!kind.is_dark());
+        assert_eq!(kind.rgb(), Some(RgbColor::from(0x808080)));
+        assert!(BackgroundKind::from_rgb(RgbColor::from(0x000080)).is_dark());
     }
 }
@@ -0,0 +1,288 @@
+/*
+ *   Copyright (c) 2023 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::*;
+use std::{
+    collections::HashMap,
+    io::Read,
+    process::{Command, Stdio},
+    sync::{Arc, Mutex, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Everything about the process' environment that color detection looks at. This makes
+/// it possible to test detection (eg: [supports_color_in]) w/ a `FakeEnvironment` (which
+/// needs the `test-utils` feature).
+///
+/// ## Example usage:
+///
+/// ```rust
+/// use r3bl_ansi_color::*;
+///
+/// /// Only has variables, the rest is left to the default methods where possible.
+/// struct VarsOnly(Vec<(&'static str, &'static str)>);
+///
+/// impl Environment for VarsOnly {
+///     fn var(&self, name: &str) -> Option<String> {
+///         let (_, value) = self.0.iter().find(|(it, _)| *it == name)?;
+///         Some(value.to_string())
+///     }
+///     fn os(&self) -> &str { "linux" }
+///     fn is_tty(&self, _: Stream) -> bool { true }
+///     fn is_ci(&self) -> bool { false }
+/// }
+///
+/// let env = VarsOnly(vec![("TERM", "xterm-256color")]);
+/// assert_eq!(env.terminfo(), None);
+/// assert_eq!(supports_color_in(&env, Stream::Stdout), ColorSupport::Ansi256);
+/// ```
+pub trait Environment {
+    /// Returns the value of the environment variable `name`, if it is set (and valid
+    /// unicode).
+    fn var(&self, name: &str) -> Option<String>;
+
+    /// The operating system, using the same names as [std::env::consts::OS], eg: `linux`,
+    /// `macos`, `windows`.
+    fn os(&self) -> &str;
+
+    fn is_tty(&self, stream: Stream) -> bool;
+
+    /// Whether this is running in a CI environment (eg: GitHub Actions).
+    fn is_ci(&self) -> bool;
+
+    /// The terminfo entry for `$TERM`, if there is one. By default there is none.
+    fn terminfo(&self) -> Option<Arc<Terminfo>> {
+        None
+    }
+
+    /// Inside of tmux, the features of the outer terminal that tmux knows about, eg:
+    /// `256,RGB,clipboard,title`. By default they are unknown.
+    fn tmux_client_termfeatures(&self) -> Option<String> {
+        None
+    }
+}
+
+/// The actual environment of this process.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealEnvironment;
+
+impl Environment for RealEnvironment {
+    fn var(&self, name: &str) -> Option<String> {
+        std::env::var(name).ok()
+    }
+
+    fn os(&self) -> &str {
+        std::env::consts::OS
+    }
+
+    fn is_tty(&self, stream: Stream) -> bool {
+        use is_terminal::IsTerminal;
+        match stream {
+            Stream::Stdout => std::io::stdout().is_terminal(),
+            Stream::Stderr => std::io::stderr().is_terminal(),
+        }
+    }
+
+    fn is_ci(&self) -> bool {
+        is_ci::uncached()
+    }
+
+    /// Searching the terminfo directories and parsing the entry happens once per
+    /// `TERM` value, the result is cached (and shared) for the lifetime of the process.
+    fn terminfo(&self) -> Option<Arc<Terminfo>> {
+        static TERMINFO: OnceLock<Mutex<HashMap<String, Option<Arc<Terminfo>>>>> = OnceLock::new();
+
+        let term = self.var("TERM")?;
+        let mut cache = TERMINFO
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(|it| it.into_inner());
+        cache
+            .entry(term)
+            .or_insert_with_key(|term| Terminfo::load_in(self, term).ok().map(Arc::new))
+            .clone()
+    }
+
+    /// Asks tmux (3.2+) w/ `tmux display-message -p -t "$TMUX_PANE"
+    /// '#{client_termfeatures}'`. This forks a process, so the answer is cached for the
+    /// lifetime of the process, and tmux is given half a second to reply.
+    fn tmux_client_termfeatures(&self) -> Option<String> {
+        static FEATURES: OnceLock<Option<String>> = OnceLock::new();
+
+        self.var("TMUX").filter(|it| !it.is_empty())?;
+        FEATURES
+            .get_or_init(|| query_tmux_client_termfeatures(self.var("TMUX_PANE")))
+            .clone()
+    }
+}
+
+/// How long to wait for `tmux display-message` before giving up on it.
+const TMUX_QUERY_TIMEOUT: Duration = Duration::from_millis(500);
+
+fn query_tmux_client_termfeatures(pane: Option<String>) -> Option<String> {
+    let mut command = Command::new("tmux");
+    command.args(["display-message", "-p"]);
+    if let Some(pane) = pane.filter(|it| !it.is_empty()) {
+        command.args(["-t", &pane]);
+    }
+    let mut child = command
+        .arg("#{client_termfeatures}")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    // `Command::output()` has no timeout, so poll the child until the deadline.
+    let deadline = Instant::now() + TMUX_QUERY_TIMEOUT;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(5)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    };
+
+    let mut features = String::new();
+    child.stdout.take()?.read_to_string(&mut features).ok()?;
+    let features = features.trim();
+    (status.success() && !features.is_empty()).then(|| features.to_string())
+}
+
+/// An environment w/ made up values, for tests. By default, no variables are set, the
+/// OS is `linux`, neither stream is a TTY, it is not CI, and there's no terminfo entry or
+/// tmux client.
+///
+/// This is only available w/ the `test-utils` feature, eg: in the `[dev-dependencies]`
+/// of a crate that wants to test how its output looks in different terminals.
+///
+/// ## Example usage:
+///
+/// ```rust
+/// use r3bl_ansi_color::*;
+///
+/// let env = FakeEnvironment::new()
+///     .os("macos")
+///     .var("TERM_PROGRAM", "iTerm.app")
+///     .tty(Stream::Stdout);
+/// assert_eq!(supports_color_in(&env, Stream::Stdout), ColorSupport::Truecolor);
+/// assert_eq!(supports_color_in(&env, Stream::Stderr), ColorSupport::NoColor);
+/// ```
+#[cfg(any(test, feature = "test-utils"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FakeEnvironment {
+    pub vars: HashMap<String, String>,
+    pub os: String,
+    pub stdout_is_tty: bool,
+    pub stderr_is_tty: bool,
+    pub is_ci: bool,
+    pub terminfo: Option<Arc<Terminfo>>,
+    pub tmux_client_termfeatures: Option<String>,
+}
+
+#[cfg(any(test, feature = "test-utils"))]
+mod fake_environment_impl {
+    use super::*;
+
+    impl Default for FakeEnvironment {
+        fn default() -> Self {
+            Self {
+                vars: HashMap::new(),
+                os: "linux".to_string(),
+                stdout_is_tty: false,
+                stderr_is_tty: false,
+                is_ci: false,
+                terminfo: None,
+                tmux_client_termfeatures: None,
+            }
+        }
+    }
+
+    impl FakeEnvironment {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn var(mut self, name: &str, value: &str) -> Self {
+            self.vars.insert(name.to_string(), value.to_string());
+            self
+        }
+
+        pub fn os(mut self, os: &str) -> Self {
+            self.os = os.to_string();
+            self
+        }
+
+        /// Makes `stream` a TTY.
+        pub fn tty(mut self, stream: Stream) -> Self {
+            match stream {
+                Stream::Stdout => self.stdout_is_tty = true,
+                Stream::Stderr => self.stderr_is_tty = true,
+            }
+            self
+        }
+
+        pub fn ci(mut self, is_ci: bool) -> Self {
+            self.is_ci = is_ci;
+            self
+        }
+
+        pub fn terminfo(mut self, terminfo: Terminfo) -> Self {
+            self.terminfo = Some(Arc::new(terminfo));
+            self
+        }
+
+        pub fn tmux_client_termfeatures(mut self, features: &str) -> Self {
+            self.tmux_client_termfeatures = Some(features.to_string());
+            self
+        }
+    }
+
+    impl Environment for FakeEnvironment {
+        fn var(&self, name: &str) -> Option<String> {
+            self.vars.get(name).cloned()
+        }
+
+        fn os(&self) -> &str {
+            &self.os
+        }
+
+        fn is_tty(&self, stream: Stream) -> bool {
+            match stream {
+                Stream::Stdout => self.stdout_is_tty,
+                Stream::Stderr => self.stderr_is_tty,
+            }
+        }
+
+        fn is_ci(&self) -> bool {
+            self.is_ci
+        }
+
+        fn terminfo(&self) -> Option<Arc<Terminfo>> {
+            self.terminfo.clone()
+        }
+
+        fn tmux_client_termfeatures(&self) -> Option<String> {
+            self.tmux_client_termfeatures.clone()
+        }
+    }
+}
@@ -0,0 +1,318 @@
+/*
+ *   Copyright (c) 2023 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+//! More info:
+//! - <https://www.w3.org/TR/css-color-4/#interpolation>
+//! - <https://bottosson.github.io/posts/oklab/>
+
+use crate::*;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The color space in which the colors between 2 stops of a [Gradient] are computed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InterpolationSpace {
+    /// Interpolates the sRGB components. This is what most tools do, and the middle of
+    /// eg: a red to green gradient looks muddy and dark.
+    Srgb,
+    /// Interpolates in linear light, which is how light physically mixes.
+    LinearRgb,
+    /// Interpolates in [Oklab], so the steps look evenly spaced.
+    #[default]
+    Oklab,
+    /// Interpolates in [HslColor], going around the hue wheel the shorter way. This keeps
+    /// colors saturated, eg: for rainbows.
+    Hsl,
+}
+
+/// Whether a gradient is applied to the foreground or the background of text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GradientTarget {
+    #[default]
+    Foreground,
+    Background,
+}
+
+/// A color gradient, made up of color stops that are evenly spaced from start to end.
+///
+/// ## Example usage:
+///
+/// ```rust
+/// use r3bl_ansi_color::*;
+///
+/// let gradient = Gradient::new([RgbColor::from(0xff0000), RgbColor::from(0x0000ff)])
+///     .with_space(InterpolationSpace::Hsl);
+/// assert_eq!(gradient.sample(3)[1], Color::Rgb(255, 0, 255));
+///
+/// let banner = gradient.render(
+///     "Hello, world!",
+///     GradientTarget::Foreground,
+///     ColorSupport::Ansi256,
+/// );
+/// banner.println();
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Gradient {
+    pub stops: Vec<RgbColor>,
+    pub space: InterpolationSpace,
+}
+
+mod gradient_impl {
+    use super::*;
+
+    impl Gradient {
+        pub fn new(stops: impl IntoIterator<Item = impl TransformColor>) -> Self {
+            Self {
+                stops: stops.into_iter().map(|it| it.as_rgb()).collect(),
+                space: InterpolationSpace::default(),
+            }
+        }
+
+        pub fn with_space(mut self, space: InterpolationSpace) -> Self {
+            self.space = space;
+            self
+        }
+
+        /// Returns the color at `t`, where `0.0` is the first stop and `1.0` is the last
+        /// one. `t` is clamped to `0.0..=1.0`. Returns [None] if there are no stops.
+        pub fn at(&self, t: f32) -> Option<RgbColor> {
+            let (first, last) = (*self.stops.first()?, *self.stops.last()?);
+            let segments = self.stops.len() - 1;
+            if segments == 0 {
+                return Some(first);
+            }
+
+            let position = t.clamp(0.0, 1.0) * segments as f32;
+            let index = position.floor() as usize;
+            if index >= segments {
+                return Some(last);
+            }
+            let (from, to) = (self.stops[index], self.stops[index + 1]);
+            Some(interpolate(from, to, position - index as f32, self.space))
+        }
+
+        /// Returns `count` colors that are evenly spaced from the first stop to the last
+        /// one (both included).
+        pub fn sample(&self, count: usize) -> Vec<Color> {
+            (0..count)
+                .filter_map(|index| {
+                    let t = match count {
+                        1 => 0.0,
+                        _ => index as f32 / (count - 1) as f32,
+                    };
+                    self.at(t).map(Color::from)
+                })
+                .collect()
+        }
+
+        /// Applies this gradient to each grapheme in `text`. The colors are quantized for
+        /// `color_support` (eg: to [Color::Ansi256]), and adjacent graphemes that end up
+        /// w/ the same color are merged into a single span.
+        pub fn render(
+            &self,
+            text: &str,
+            target: GradientTarget,
+            color_support: ColorSupport,
+        ) -> StyledLine {
+            let graphemes: Vec<&str> = text.graphemes(true).collect();
+            let colors = self.sample(graphemes.len());
+
+            let mut acc = StyledLine::new();
+            if colors.is_empty() || color_support == ColorSupport::NoColor {
+                acc.push(text.styled());
+                return acc;
+            }
+
+            let mut current: Option<OwnedStyledText> = None;
+            for (grapheme, color) in graphemes.into_iter().zip(colors) {
+                let color = color.quantize(color_support);
+                let style = match target {
+                    GradientTarget::Foreground => Style::Foreground(color),
+                    GradientTarget::Background => Style::Background(color),
+                };
+                match &mut current {
+                    Some(span) if span.style[0] == style => span.text.push_str(grapheme),
+                    _ => {
+                        if let Some(span) = current.take() {
+                            acc.push(span);
+                        }
+                        current = Some(OwnedStyledText {
+                            text: grapheme.to_string(),
+                            style: vec![style],
+                        });
+                    }
+                }
+            }
+            if let Some(span) = current {
+                acc.push(span);
+            }
+
+            acc
+        }
+    }
+
+    fn lerp(from: f32, to: f32, t: f32) -> f32 {
+        from + (to - from) * t
+    }
+
+    fn interpolate(from: RgbColor, to: RgbColor, t: f32, space: InterpolationSpace) -> RgbColor {
+        match space {
+            InterpolationSpace::Srgb => {
+                let channel = |from: u8, to: u8| lerp(from as f32, to as f32, t).round() as u8;
+                RgbColor {
+                    red: channel(from.red, to.red),
+                    green: channel(from.green, to.green),
+                    blue: channel(from.blue, to.blue),
+                }
+            }
+            InterpolationSpace::LinearRgb => {
+                let channel = |from: u8, to: u8| {
+                    linear_to_srgb(lerp(srgb_to_linear(from), srgb_to_linear(to), t))
+                };
+                RgbColor {
+                    red: channel(from.red, to.red),
+                    green: channel(from.green, to.green),
+                    blue: channel(from.blue, to.blue),
+                }
+            }
+            InterpolationSpace::Oklab => from.mix(to, t),
+            InterpolationSpace::Hsl => {
+                let (from, to) = (HslColor::from(from), HslColor::from(to));
+                // The hue of a gray is meaningless, so use the other color's hue.
+                let (from_hue, to_hue) = match (from.saturation == 0.0, to.saturation == 0.0) {
+                    (true, false) => (to.hue, to.hue),
+                    (false, true) => (from.hue, from.hue),
+                    _ => (from.hue, to.hue),
+                };
+                // Go around the hue wheel the shorter way.
+                let delta = (to_hue - from_hue + 180.0).rem_euclid(360.0) - 180.0;
+                HslColor::new(
+                    from_hue + delta * t,
+                    lerp(from.saturation, to.saturation, t),
+                    lerp(from.lightness, to.lightness, t),
+                )
+                .into()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const RED: RgbColor = RgbColor {
+        red: 255,
+        green: 0,
+        blue: 0,
+    };
+    const LIME: RgbColor = RgbColor {
+        red: 0,
+        green: 255,
+        blue: 0,
+    };
+
+    #[test]
+    fn test_sample_in_each_space() {
+        let middle = |space| Gradient::new([RED, LIME]).with_space(space).sample(3)[1];
+        assert_eq!(middle(InterpolationSpace::Srgb), Color::Rgb(128, 128, 0));
+        assert_eq!(
+            middle(InterpolationSpace::LinearRgb),
+            Color::Rgb(188, 188, 0)
+        );
+        assert_eq!(middle(InterpolationSpace::Hsl), Color::Rgb(255, 255, 0));
+        assert_eq!(
+            middle(InterpolationSpace::Oklab),
+            Color::from(RED.mix(LIME, 0.5))
+        );
+
+        let gradient = Gradient::new([RED, LIME, RgbColor::from(0x0000ff)]);
+        let samples = gradient.sample(5);
+        assert_eq!(samples.len(), 5);
+        assert_eq!(samples[0], Color::Rgb(255, 0, 0));
+        assert_eq!(samples[2], Color::Rgb(0, 255, 0));
+        assert_eq!(samples[4], Color::Rgb(0, 0, 255));
+
+        assert_eq!(Gradient::new([RED]).sample(2), vec![Color::from(RED); 2]);
+        assert_eq!(Gradient::new(Vec::<RgbColor>::new()).sample(2), vec![]);
+    }
+
+    #[test]
+    fn test_hsl_takes_the_shortest_hue_path() {
+        // From hue 350 to hue 10 goes through red (0), not through cyan (180).
+        let gradient = Gradient::new([
+            HslColor::new(350.0, 1.0, 0.5),
+            HslColor::new(10.0, 1.0, 0.5),
+        ])
+        .with_space(InterpolationSpace::Hsl);
+        assert_eq!(gradient.sample(3)[1], Color::Rgb(255, 0, 0));
+
+        // Grays take the hue of the other color.
+        let gradient = Gradient::new([RgbColor::from(0x808080), RgbColor::from(0x0000ff)])
+            .with_space(InterpolationSpace::Hsl);
+        let middle = HslColor::from(gradient.at(0.5).unwrap());
+        assert!((middle.hue - 240.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_render_truecolor() {
+        let line = Gradient::new([RED, LIME]).render(
+            "a👍🏽b",
+            GradientTarget::Background,
+            ColorSupport::Truecolor,
+        );
+        assert_eq!(line.spans.len(), 3);
+        assert_eq!(line.spans[1].text, "👍🏽");
+        assert_eq!(
+            line.spans[0].style,
+            vec![Style::Background(Color::Rgb(255, 0, 0))]
+        );
+        assert_eq!(
+            line.spans[2].style,
+            vec![Style::Background(Color::Rgb(0, 255, 0))]
+        );
+    }
+
+    #[test]
+    fn test_render_collapses_repeated_codes_in_ansi256() {
+        let text = "0123456789";
+        let gradient = Gradient::new([RgbColor::from(0x000000), RgbColor::from(0x090909)])
+            .with_space(InterpolationSpace::Srgb);
+
+        let line = gradient.render(text, GradientTarget::Foreground, ColorSupport::Truecolor);
+        assert_eq!(line.spans.len(), 10);
+
+        let line = gradient.render(text, GradientTarget::Foreground, ColorSupport::Ansi256);
+        assert!(line.spans.len() < 10);
+        assert_eq!(
+            line.to_string_with(ColorSupport::NoColor, NoColorStyle::PlainText),
+            text
+        );
+        for span in &line.spans {
+            assert!(matches!(
+                span.style[..],
+                [Style::Foreground(Color::Ansi256(_))]
+            ));
+        }
+        for pair in line.spans.windows(2) {
+            assert_ne!(pair[0].style, pair[1].style);
+        }
+
+        let line = gradient.render(text, GradientTarget::Foreground, ColorSupport::NoColor);
+        assert_eq!(line.spans, vec![text.styled()]);
+    }
+}
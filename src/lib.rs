@@ -77,6 +77,20 @@
 //! .println();
 //! ```
 //!
+//! If the text and styles are produced at runtime, or have to be returned from a function,
+//! use `OwnedStyledText` instead. It can be created fluently from a `&str` or `String`.
+//!
+//! ```rust
+//! use r3bl_ansi_color::*;
+//!
+//! "Print an owned string w/ a builder."
+//!     .styled()
+//!     .bold()
+//!     .fg(Color::Rgb(50, 50, 50))
+//!     .bg(Color::Ansi256(150))
+//!     .println();
+//! ```
+//!
 //! Please a look at the [`main`
 //! example](https://github.com/r3bl-org/r3bl_ansi_color/blob/main/examples/main.rs) to get a
 //! better idea of how to use this crate.
@@ -111,9 +125,9 @@
 //! - Docs
 //!   1. `doc.fish` - generate the Rust docs.
 //!   1. `serve-doc.fish` - serve the Rust docs on `http://localhost:3000`. This is useful when using
-//!       SSH to connect to a remote machine to actually do development work on and you want to view the
-//!       docs on your local machine. Make sure you have `node` and `npm` and `serve` installed. If not
-//!       you can get it with [`brew`](https://brew.sh/).
+//!      SSH to connect to a remote machine to actually do development work on and you want to view the
+//!      docs on your local machine. Make sure you have `node` and `npm` and `serve` installed. If not
+//!      you can get it with [`brew`](https://brew.sh/).
 //!
 //! ## References
 //! <a id="markdown-references" name="references"></a>
@@ -154,16 +168,50 @@
 #![warn(rust_2018_idioms)]
 
 // Attach.
+pub mod ansi256_conversion_cache;
 pub mod ansi_escape_codes;
+pub mod ansi_escape_parser;
 pub mod ansi_styled_text;
 pub mod color;
+pub mod color_adapting_writer;
+pub mod color_manipulation;
+pub mod color_models;
+pub mod color_space;
 pub mod color_support_override;
+pub mod contrast;
 pub mod convert;
 pub mod detect_color_support;
+pub mod environment;
+pub mod gradient;
+pub mod multiplexer;
+pub mod palette;
+pub mod parse_color;
+pub mod strip_ansi;
+pub mod styled_line;
+pub mod terminal_probe;
+pub mod terminal_query;
+pub mod terminfo;
 
+pub use ansi256_conversion_cache::*;
 pub use ansi_escape_codes::*;
+pub use ansi_escape_parser::*;
 pub use ansi_styled_text::*;
 pub use color::*;
+pub use color_adapting_writer::*;
+pub use color_manipulation::*;
+pub use color_models::*;
+pub use color_space::*;
 pub use color_support_override::*;
+pub use contrast::*;
 pub use convert::*;
 pub use detect_color_support::*;
+pub use environment::*;
+pub use gradient::*;
+pub use multiplexer::*;
+pub use palette::*;
+pub use parse_color::*;
+pub use strip_ansi::*;
+pub use styled_line::*;
+pub use terminal_probe::*;
+pub use terminal_query::*;
+pub use terminfo::*;
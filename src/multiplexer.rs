@@ -0,0 +1,316 @@
+/*
+ *   Copyright (c) 2023 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+//! tmux and GNU screen sit between the program and the terminal. They interpret the
+//! escape sequences themselves, and only pass on what they understand. SGR sequences
+//! are fine, but eg: OSC queries, images, and some hyperlinks have to be wrapped in a DCS
+//! passthrough sequence to reach the outer terminal. [PassthroughWriter] does this.
+//!
+//! tmux 3.3+ only forwards passthrough sequences when `allow-passthrough` is on, eg:
+//! `set -g allow-passthrough on` in `~/.tmux.conf`.
+//!
+//! More info:
+//! - <https://github.com/tmux/tmux/wiki/FAQ#what-is-the-passthrough-escape-sequence-and-how-do-i-use-it>
+//! - <https://www.gnu.org/software/screen/manual/screen.html#Control-Sequences>
+
+use crate::*;
+use std::io::{Result, Write};
+
+/// GNU screen drops DCS strings that are longer than this, so longer sequences are split
+/// into several DCS strings.
+const SCREEN_MAX_DCS_LEN: usize = 768;
+
+/// Escape sequences that are longer than this are passed through as is, instead of
+/// waiting for them to be terminated.
+const MAX_PENDING_ESCAPE_LEN: usize = 1 << 20;
+
+/// A terminal multiplexer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Multiplexer {
+    Tmux,
+    Screen,
+}
+
+mod multiplexer_impl {
+    use super::*;
+
+    impl Multiplexer {
+        /// Detects the multiplexer that this process runs in, if any.
+        pub fn detect() -> Option<Multiplexer> {
+            Self::detect_in(&RealEnvironment)
+        }
+
+        /// Same as [Multiplexer::detect], but the environment variables are looked up in
+        /// `env`. tmux sets `TMUX`, and screen sets `STY`. `TERM=tmux*` is also checked,
+        /// since it is still set when `ssh` is run inside of tmux.
+        pub fn detect_in(env: &impl Environment) -> Option<Multiplexer> {
+            if env.var("TMUX").is_some_and(|it| !it.is_empty())
+                || env.var("TERM").is_some_and(|it| it.starts_with("tmux"))
+            {
+                Some(Multiplexer::Tmux)
+            } else if env.var("STY").is_some_and(|it| !it.is_empty()) {
+                Some(Multiplexer::Screen)
+            } else {
+                None
+            }
+        }
+
+        /// Wraps a single escape `sequence` in a DCS passthrough sequence, so that it
+        /// reaches the outer terminal.
+        ///
+        /// GNU screen ends its DCS string at the first ST (`ESC \`), and there is no
+        /// way to escape it. An OSC sequence is terminated w/ BEL instead, but DCS and
+        /// APC sequences (eg: kitty images) have to end w/ ST, so for screen they are
+        /// returned as is (and screen drops them), instead of leaking the rest of the
+        /// sequence into the terminal.
+        ///
+        /// ```rust
+        /// use r3bl_ansi_color::*;
+        ///
+        /// assert_eq!(
+        ///     Multiplexer::Tmux.wrap_passthrough(b"\x1b]11;?\x1b\\"),
+        ///     b"\x1bPtmux;\x1b\x1b]11;?\x1b\x1b\\\x1b\\"
+        /// );
+        /// ```
+        pub fn wrap_passthrough(&self, sequence: &[u8]) -> Vec<u8> {
+            let mut acc = Vec::with_capacity(sequence.len() + 16);
+            match self {
+                // Every ESC in the sequence is doubled.
+                Multiplexer::Tmux => {
+                    acc.extend_from_slice(b"\x1bPtmux;");
+                    for byte in sequence {
+                        if *byte == ESC {
+                            acc.push(ESC);
+                        }
+                        acc.push(*byte);
+                    }
+                    acc.extend_from_slice(b"\x1b\\");
+                }
+                // The sequence can't contain ST, since that would end the DCS string, so
+                // an OSC sequence is terminated w/ BEL instead.
+                Multiplexer::Screen => {
+                    let sequence = match sequence.strip_suffix(b"\x1b\\") {
+                        Some(it) if sequence.starts_with(b"\x1b]") => [it, &[BEL]].concat(),
+                        _ => sequence.to_vec(),
+                    };
+                    if sequence.windows(2).any(|it| it == b"\x1b\\") {
+                        return sequence;
+                    }
+                    for chunk in sequence.chunks(SCREEN_MAX_DCS_LEN) {
+                        acc.extend_from_slice(b"\x1bP");
+                        acc.extend_from_slice(chunk);
+                        acc.extend_from_slice(b"\x1b\\");
+                    }
+                }
+            }
+            acc
+        }
+    }
+}
+
+/// Whether `sequence` has to be wrapped to reach the outer terminal. OSC (except for the
+/// window title, which the multiplexer keeps track of), DCS and APC sequences (eg:
+/// sixel and kitty images) are wrapped. CSI sequences (including SGR) and the other
+/// escapes are interpreted by the multiplexer itself.
+pub fn needs_passthrough(sequence: &EscapeSequence<'_>) -> bool {
+    match sequence {
+        EscapeSequence::Osc { payload } => !matches!(
+            payload,
+            [b'0' | b'1' | b'2', b';', ..] | [b'0' | b'1' | b'2']
+        ),
+        EscapeSequence::Dcs { kind, payload } => !(*kind == b'P' && payload.starts_with(b"tmux;")),
+        EscapeSequence::Csi { .. } | EscapeSequence::Other { .. } => false,
+    }
+}
+
+/// Wraps any [Write], and wraps the escape sequences that pass through it in a DCS
+/// passthrough sequence (see [needs_passthrough]) for the given [Multiplexer]. Everything
+/// else is passed through as is, and so is everything when there is no multiplexer.
+///
+/// Escape sequences that are split across `write` calls are buffered until they are
+/// complete. Partial writes and dropping the writer are handled the same way as in
+/// [ColorAdaptingWriter].
+///
+/// ## Example usage:
+///
+/// ```rust
+/// use r3bl_ansi_color::*;
+/// use std::io::Write;
+///
+/// let mut writer = PassthroughWriter::new(vec![], Some(Multiplexer::Tmux));
+/// writer.write_all(b"\x1b[1mHi\x1b]8;;https://r3bl.com\x07").unwrap();
+/// assert_eq!(
+///     writer.into_inner().unwrap(),
+///     b"\x1b[1mHi\x1bPtmux;\x1b\x1b]8;;https://r3bl.com\x07\x1b\\"
+/// );
+/// ```
+#[derive(Debug)]
+pub struct PassthroughWriter<W: Write> {
+    writer: EscapeRewritingWriter<W>,
+    multiplexer: Option<Multiplexer>,
+}
+
+mod passthrough_writer_impl {
+    use super::*;
+
+    impl<W: Write> PassthroughWriter<W> {
+        pub fn new(inner: W, multiplexer: Option<Multiplexer>) -> Self {
+            Self {
+                writer: EscapeRewritingWriter::new(inner, MAX_PENDING_ESCAPE_LEN),
+                multiplexer,
+            }
+        }
+
+        /// Uses [Multiplexer::detect].
+        pub fn detect(inner: W) -> Self {
+            Self::new(inner, Multiplexer::detect())
+        }
+
+        pub fn multiplexer(&self) -> Option<Multiplexer> {
+            self.multiplexer
+        }
+
+        pub fn get_ref(&self) -> &W {
+            self.writer.get_ref()
+        }
+
+        pub fn get_mut(&mut self) -> &mut W {
+            self.writer.get_mut()
+        }
+
+        /// Writes everything that is still buffered, including an incomplete escape
+        /// sequence (as is), and returns the wrapped writer.
+        pub fn into_inner(self) -> Result<W> {
+            self.writer.into_inner()
+        }
+    }
+
+    impl<W: Write> Write for PassthroughWriter<W> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            // Nothing is ever buffered w/out a multiplexer.
+            let Some(multiplexer) = self.multiplexer else {
+                return self.writer.get_mut().write(buf);
+            };
+
+            self.writer
+                .write_rewritten(buf, |sequence, original, output| {
+                    if needs_passthrough(sequence) {
+                        output.extend(multiplexer.wrap_passthrough(original));
+                    } else {
+                        output.extend_from_slice(original);
+                    }
+                })
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            self.writer.flush()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn write_through(multiplexer: Option<Multiplexer>, input: &[u8], chunk_size: usize) -> Vec<u8> {
+        let mut writer = PassthroughWriter::new(vec![], multiplexer);
+        for chunk in input.chunks(chunk_size) {
+            writer.write_all(chunk).unwrap();
+        }
+        writer.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_detect_in() {
+        let detect = |vars: &[(&str, &str)]| {
+            let env = vars
+                .iter()
+                .fold(FakeEnvironment::new(), |env, (name, value)| {
+                    env.var(name, value)
+                });
+            Multiplexer::detect_in(&env)
+        };
+        assert_eq!(
+            detect(&[("TMUX", "/tmp/tmux-1000/default,1234,0")]),
+            Some(Multiplexer::Tmux)
+        );
+        assert_eq!(
+            detect(&[("TERM", "tmux-256color")]),
+            Some(Multiplexer::Tmux)
+        );
+        assert_eq!(
+            detect(&[("STY", "1234.pts-0.host"), ("TERM", "screen")]),
+            Some(Multiplexer::Screen)
+        );
+        // tmux inside of screen.
+        assert_eq!(
+            detect(&[("STY", "1234.pts-0.host"), ("TMUX", "/tmp/tmux")]),
+            Some(Multiplexer::Tmux)
+        );
+        assert_eq!(detect(&[("TERM", "screen-256color")]), None);
+        assert_eq!(detect(&[]), None);
+    }
+
+    #[test]
+    fn test_wrap_passthrough() {
+        assert_eq!(
+            Multiplexer::Tmux.wrap_passthrough(b"\x1b_Gf=100;AAAA\x1b\\"),
+            b"\x1bPtmux;\x1b\x1b_Gf=100;AAAA\x1b\x1b\\\x1b\\"
+        );
+        assert_eq!(
+            Multiplexer::Screen.wrap_passthrough(b"\x1b]11;?\x1b\\"),
+            b"\x1bP\x1b]11;?\x07\x1b\\"
+        );
+        // The ST at the end would close screen's DCS string early.
+        assert_eq!(
+            Multiplexer::Screen.wrap_passthrough(b"\x1b_Gf=100;AAAA\x1b\\"),
+            b"\x1b_Gf=100;AAAA\x1b\\"
+        );
+        assert_eq!(
+            Multiplexer::Screen.wrap_passthrough(b"\x1bPq#0;2;0;0;0\x1b\\"),
+            b"\x1bPq#0;2;0;0;0\x1b\\"
+        );
+
+        let long = [b"\x1b]1337;File=:".as_slice(), &[b'A'; 1000], b"\x07"].concat();
+        let wrapped = Multiplexer::Screen.wrap_passthrough(&long);
+        assert_eq!(
+            wrapped,
+            [
+                b"\x1bP".as_slice(),
+                &long[..SCREEN_MAX_DCS_LEN],
+                b"\x1b\\\x1bP",
+                &long[SCREEN_MAX_DCS_LEN..],
+                b"\x1b\\"
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn test_writer_only_wraps_what_needs_passthrough() {
+        let input = b"\x1b]0;title\x07\x1b[31mred\x1b[0m\x1b[2K\x1b]11;?\x1b\\\x1bPtmux;\x1b\x1b]4;1;?\x07\x1b\\";
+        let expected = b"\x1b]0;title\x07\x1b[31mred\x1b[0m\x1b[2K\x1bPtmux;\x1b\x1b]11;?\x1b\x1b\\\x1b\\\x1bPtmux;\x1b\x1b]4;1;?\x07\x1b\\";
+        for chunk_size in [1, 3, input.len()] {
+            assert_eq!(
+                write_through(Some(Multiplexer::Tmux), input, chunk_size),
+                expected
+            );
+        }
+        assert_eq!(write_through(None, input, 3), input);
+    }
+}
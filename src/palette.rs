@@ -0,0 +1,505 @@
+/*
+ *   Copyright (c) 2023 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+//! The colors that a terminal actually shows for each of the 256 palette indices. The
+//! 16 system colors in particular depend on the terminal and the user's theme, so
+//! converting eg: [Ansi256Color] to [RgbColor], or finding the nearest [Ansi16Color],
+//! is only accurate if it uses the right palette.
+//!
+//! The active palette is global, and it is [Palette::XTERM] by default. It is used by
+//! [TransformColor] for the ANSI colors, and by [convert_rgb_into_ansi16] and
+//! [convert_rgb_into_ansi256_using].
+//!
+//! ## Example usage:
+//!
+//! ```rust
+//! use r3bl_ansi_color::*;
+//!
+//! palette_set(Palette::CAMPBELL);
+//! assert_eq!(Ansi256Color { index: 4 }.as_rgb(), RgbColor::from(0x0037da));
+//! assert_eq!(RgbColor::from(0x3b78ff).as_ansi16(), Ansi16Color::new(12));
+//! ```
+//!
+//! More info:
+//! - <https://ethanschoonover.com/solarized/>
+//! - <https://draculatheme.com/contribute>
+//! - <https://learn.microsoft.com/en-us/windows/terminal/customize-settings/color-schemes>
+
+use crate::*;
+use std::{
+    fmt::{Display, Formatter},
+    path::Path,
+    sync::RwLock,
+};
+
+/// RGB values for each of the 256 palette indices.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: [RgbColor; 256],
+}
+
+static ACTIVE_PALETTE: RwLock<Palette> = RwLock::new(Palette::XTERM);
+
+/// Makes `palette` the one that is used by all the conversions. This also clears the
+/// conversion cache, see [ansi256_conversion_cache_clear].
+pub fn palette_set(palette: Palette) {
+    *ACTIVE_PALETTE.write().unwrap_or_else(|it| it.into_inner()) = palette;
+    ansi256_conversion_cache_clear();
+}
+
+pub fn palette_get() -> Palette {
+    with_active_palette(Palette::clone)
+}
+
+/// Runs `f` w/ the active palette, without copying it.
+pub(crate) fn with_active_palette<R>(f: impl FnOnce(&Palette) -> R) -> R {
+    f(&ACTIVE_PALETTE.read().unwrap_or_else(|it| it.into_inner()))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaletteParseError {
+    pub kind: PaletteParseErrorKind,
+    /// 1 based line number.
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaletteParseErrorKind {
+    /// The index in `colorN` is not in `0..=255`.
+    InvalidIndex(String),
+    /// The value can't be parsed as a color.
+    InvalidColor(ColorParseError),
+}
+
+#[derive(Debug)]
+pub enum PaletteLoadError {
+    Io(std::io::Error),
+    Parse(PaletteParseError),
+}
+
+mod palette_error_impl {
+    use super::*;
+
+    impl Display for PaletteParseError {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            let line = self.line;
+            match &self.kind {
+                PaletteParseErrorKind::InvalidIndex(index) => {
+                    write!(f, "invalid palette index `{index}` on line {line}")
+                }
+                PaletteParseErrorKind::InvalidColor(error) => {
+                    write!(f, "{error}, on line {line}")
+                }
+            }
+        }
+    }
+
+    impl std::error::Error for PaletteParseError {}
+
+    impl Display for PaletteLoadError {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            match self {
+                PaletteLoadError::Io(error) => write!(f, "can't read palette: {error}"),
+                PaletteLoadError::Parse(error) => write!(f, "can't parse palette: {error}"),
+            }
+        }
+    }
+
+    impl std::error::Error for PaletteLoadError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            match self {
+                PaletteLoadError::Io(error) => Some(error),
+                PaletteLoadError::Parse(error) => Some(error),
+            }
+        }
+    }
+
+    impl From<std::io::Error> for PaletteLoadError {
+        fn from(error: std::io::Error) -> Self {
+            PaletteLoadError::Io(error)
+        }
+    }
+
+    impl From<PaletteParseError> for PaletteLoadError {
+        fn from(error: PaletteParseError) -> Self {
+            PaletteLoadError::Parse(error)
+        }
+    }
+}
+
+mod palette_impl {
+    use super::*;
+
+    const fn rgb(value: u32) -> RgbColor {
+        RgbColor {
+            red: (value >> 16) as u8,
+            green: (value >> 8) as u8,
+            blue: value as u8,
+        }
+    }
+
+    #[rustfmt::skip]
+    impl Palette {
+        /// The default xterm colors, see [constants::ANSI_COLOR_PALETTE].
+        pub const XTERM: Palette = Palette::from_u32(&constants::ANSI_COLOR_PALETTE);
+
+        pub const SOLARIZED_DARK: Palette = Palette::XTERM.with_system_colors([
+            0x073642, 0xdc322f, 0x859900, 0xb58900, 0x268bd2, 0xd33682, 0x2aa198, 0xeee8d5,
+            0x002b36, 0xcb4b16, 0x586e75, 0x657b83, 0x839496, 0x6c71c4, 0x93a1a1, 0xfdf6e3,
+        ]);
+
+        pub const DRACULA: Palette = Palette::XTERM.with_system_colors([
+            0x21222c, 0xff5555, 0x50fa7b, 0xf1fa8c, 0xbd93f9, 0xff79c6, 0x8be9fd, 0xf8f8f2,
+            0x6272a4, 0xff6e6e, 0x69ff94, 0xffffa5, 0xd6acff, 0xff92df, 0xa4ffff, 0xffffff,
+        ]);
+
+        /// The default color scheme of Windows Terminal.
+        pub const CAMPBELL: Palette = Palette::XTERM.with_system_colors([
+            0x0c0c0c, 0xc50f1f, 0x13a10e, 0xc19c00, 0x0037da, 0x881798, 0x3a96dd, 0xcccccc,
+            0x767676, 0xe74856, 0x16c60c, 0xf9f1a5, 0x3b78ff, 0xb4009e, 0x61d6d6, 0xf2f2f2,
+        ]);
+    }
+
+    impl Palette {
+        pub const fn from_u32(colors: &[u32; 256]) -> Palette {
+            let mut acc = [rgb(0); 256];
+            let mut index = 0;
+            while index < 256 {
+                acc[index] = rgb(colors[index]);
+                index += 1;
+            }
+            Palette { colors: acc }
+        }
+
+        /// Returns a copy of `self` w/ the 16 system colors replaced.
+        pub const fn with_system_colors(&self, system_colors: [u32; 16]) -> Palette {
+            let mut acc = Palette {
+                colors: self.colors,
+            };
+            let mut index = 0;
+            while index < 16 {
+                acc.colors[index] = rgb(system_colors[index]);
+                index += 1;
+            }
+            acc
+        }
+
+        /// Returns one of the presets by name (case insensitive): `xterm`,
+        /// `solarized-dark`, `dracula`, or `campbell`.
+        pub fn preset(name: &str) -> Option<Palette> {
+            match name.to_ascii_lowercase().as_str() {
+                "xterm" => Some(Palette::XTERM),
+                "solarized-dark" | "solarized_dark" | "solarized" => Some(Palette::SOLARIZED_DARK),
+                "dracula" => Some(Palette::DRACULA),
+                "campbell" => Some(Palette::CAMPBELL),
+                _ => None,
+            }
+        }
+
+        /// Parses a palette in one of these formats, one color per line:
+        /// - `color0 = #000000` or `color0 #000000` (eg: kitty).
+        /// - `*.color0: #000000` or `*color0: #000000` (Xresources).
+        ///
+        /// Colors can be in any form that [parse_color()] accepts. Lines that start w/ `#`
+        /// or `!` are comments, and lines w/ other keys (eg: `background`, or
+        /// `colorscheme`) are ignored.
+        /// Indices that are not in the input keep their [Palette::XTERM] colors.
+        pub fn parse(input: &str) -> Result<Palette, PaletteParseError> {
+            let mut acc = Palette::XTERM;
+
+            for (line_index, line) in input.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+                    continue;
+                }
+
+                let Some(separator) =
+                    line.find(|it: char| it == '=' || it == ':' || it.is_whitespace())
+                else {
+                    continue;
+                };
+                let (key, value) = (&line[..separator], line[separator + 1..].trim());
+                let value = value.trim_start_matches(['=', ':']).trim();
+
+                // Remove the Xresources prefix, eg: `*.` or `URxvt.`.
+                let key = match key.rfind(['*', '.']) {
+                    Some(index) => &key[index + 1..],
+                    None => key,
+                };
+                // Skip other keys that start w/ `color`, eg: `colorscheme` or `color_fg`.
+                let Some(index) = key
+                    .to_ascii_lowercase()
+                    .strip_prefix("color")
+                    .filter(|it| !it.is_empty() && it.bytes().all(|it| it.is_ascii_digit()))
+                    .map(String::from)
+                else {
+                    continue;
+                };
+
+                let error = |kind| PaletteParseError {
+                    kind,
+                    line: line_index + 1,
+                };
+                let index: u8 = index
+                    .parse()
+                    .map_err(|_| error(PaletteParseErrorKind::InvalidIndex(index.clone())))?;
+                let color: RgbColor = value
+                    .parse()
+                    .map_err(|it| error(PaletteParseErrorKind::InvalidColor(it)))?;
+                acc.colors[index as usize] = color;
+            }
+
+            Ok(acc)
+        }
+
+        /// Reads and parses a palette file, see [Palette::parse].
+        pub fn load(path: impl AsRef<Path>) -> Result<Palette, PaletteLoadError> {
+            let input = std::fs::read_to_string(path)?;
+            Ok(Palette::parse(&input)?)
+        }
+
+        pub fn rgb(&self, color: Ansi256Color) -> RgbColor {
+            self.colors[color.index as usize]
+        }
+
+        /// Finds the closest of the 16 system colors to `rgb_color`.
+        pub fn nearest_ansi16(&self, rgb_color: RgbColor) -> Ansi16Color {
+            let index = closest_index(&self.colors[..16], |candidate| {
+                calculate_relative_diff_between_colors(rgb_color, candidate) as f32
+            });
+            Ansi16Color::new(index)
+        }
+
+        /// Finds the closest color to `rgb_color`. The 16 system colors are only picked
+        /// if they are closer than all the other colors, so a color that is in the 6×6×6
+        /// cube (or the grays) keeps its index there, which doesn't depend on the theme.
+        pub fn nearest_ansi256(
+            &self,
+            rgb_color: RgbColor,
+            strategy: Ansi256ConversionStrategy,
+        ) -> Ansi256Color {
+            match strategy {
+                Ansi256ConversionStrategy::Redmean => {
+                    let distance = |candidate| {
+                        calculate_relative_diff_between_colors(rgb_color, candidate) as f32
+                    };
+                    let index = if self.colors[16..] == Palette::XTERM.colors[16..] {
+                        convert_rgb_into_ansi256(rgb_color).index
+                    } else {
+                        closest_index(&self.colors[16..], distance) + 16
+                    };
+                    self.nearest_including_system_colors(index, distance)
+                }
+                Ansi256ConversionStrategy::Oklab => {
+                    let target = Oklab::from(rgb_color);
+                    let distance = |candidate| target.distance_squared(&Oklab::from(candidate));
+                    let index = closest_index(&self.colors[16..], distance) + 16;
+                    self.nearest_including_system_colors(index, distance)
+                }
+                Ansi256ConversionStrategy::Ciede2000 => {
+                    let target = CieLab::from(rgb_color);
+                    let distance = |candidate| target.ciede2000(&CieLab::from(candidate));
+                    let index = closest_index(&self.colors[16..], distance) + 16;
+                    self.nearest_including_system_colors(index, distance)
+                }
+            }
+        }
+
+        /// Returns the closest system color if it is closer than `self.colors[index]`,
+        /// otherwise `index`.
+        fn nearest_including_system_colors(
+            &self,
+            index: u8,
+            distance: impl Fn(RgbColor) -> f32,
+        ) -> Ansi256Color {
+            let system_index = closest_index(&self.colors[..16], &distance);
+            if distance(self.colors[system_index as usize]) < distance(self.colors[index as usize])
+            {
+                Ansi256Color {
+                    index: system_index,
+                }
+            } else {
+                Ansi256Color { index }
+            }
+        }
+    }
+
+    impl Default for Palette {
+        fn default() -> Self {
+            Palette::XTERM
+        }
+    }
+
+    /// Returns the index of the entry in `colors` w/ the smallest `distance`.
+    fn closest_index(colors: &[RgbColor], distance: impl Fn(RgbColor) -> f32) -> u8 {
+        let mut closest_index = 0;
+        let mut closest_distance = f32::MAX;
+        for (index, color) in colors.iter().enumerate() {
+            let distance = distance(*color);
+            if distance < closest_distance {
+                closest_index = index;
+                closest_distance = distance;
+            }
+        }
+        closest_index as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_presets() {
+        assert_eq!(
+            Palette::XTERM.rgb(Ansi256Color { index: 1 }),
+            RgbColor::from(0xcd0000)
+        );
+        assert_eq!(
+            Palette::DRACULA.rgb(Ansi256Color { index: 1 }),
+            RgbColor::from(0xff5555)
+        );
+        // Only the system colors are different.
+        assert_eq!(Palette::CAMPBELL.colors[16..], Palette::XTERM.colors[16..]);
+        assert_eq!(
+            Palette::preset("Solarized-Dark"),
+            Some(Palette::SOLARIZED_DARK)
+        );
+        assert_eq!(Palette::preset("nope"), None);
+    }
+
+    #[test]
+    fn test_nearest_color_targets_the_palette() {
+        // Solarized's "bright black" is a dark teal, not gray.
+        let color = RgbColor::from(0x002b36);
+        assert_eq!(Palette::XTERM.nearest_ansi16(color), Ansi16Color::new(0));
+        assert_eq!(
+            Palette::SOLARIZED_DARK.nearest_ansi16(color),
+            Ansi16Color::new(8)
+        );
+
+        // Windows Terminal's blue is quite different from xterm's.
+        let color = RgbColor::from(0x3b78ff);
+        assert_eq!(
+            Palette::CAMPBELL.nearest_ansi16(color),
+            Ansi16Color::new(12)
+        );
+
+        let mut palette = Palette::XTERM;
+        palette.colors[200] = RgbColor::from(0x123456);
+        assert_eq!(
+            palette.nearest_ansi256(RgbColor::from(0x123457), Ansi256ConversionStrategy::Oklab),
+            Ansi256Color { index: 200 }
+        );
+    }
+
+    #[test]
+    fn test_nearest_ansi256_searches_the_whole_palette() {
+        use Ansi256ConversionStrategy::*;
+
+        let color = RgbColor::from(0x123457);
+        let mut palette = Palette::XTERM;
+        palette.colors[200] = RgbColor::from(0x123456);
+        let mut system_palette = Palette::XTERM;
+        system_palette.colors[4] = RgbColor::from(0x123456);
+
+        for strategy in [Redmean, Oklab, Ciede2000] {
+            assert_ne!(
+                Palette::XTERM.nearest_ansi256(color, strategy),
+                Ansi256Color { index: 200 }
+            );
+            assert_eq!(
+                palette.nearest_ansi256(color, strategy),
+                Ansi256Color { index: 200 }
+            );
+            assert_eq!(
+                system_palette.nearest_ansi256(color, strategy),
+                Ansi256Color { index: 4 }
+            );
+            // Colors that are in the cube keep their index, even if a system color is
+            // the same.
+            assert_eq!(
+                Palette::XTERM.nearest_ansi256(RgbColor::from(0xff0000), strategy),
+                Ansi256Color { index: 196 }
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let input = "# kitty\n\
+                     color0 #101010\n\
+                     color1 = rgb(200, 0, 0)\n\
+                     background #000000\n\
+                     ! Xresources\n\
+                     *.color2: #00aa00\n\
+                     URxvt.color3:  yellow\n\
+                     *color255: #eeeeee\n\
+                     colorscheme = dark\n\
+                     color_fg: #ffffff\n";
+        let palette = Palette::parse(input).unwrap();
+        assert_eq!(palette.colors[0], RgbColor::from(0x101010));
+        assert_eq!(palette.colors[1], RgbColor::from(0xc80000));
+        assert_eq!(palette.colors[2], RgbColor::from(0x00aa00));
+        assert_eq!(palette.colors[3], RgbColor::from(0xffff00));
+        assert_eq!(palette.colors[4], Palette::XTERM.colors[4]);
+        assert_eq!(palette.colors[255], RgbColor::from(0xeeeeee));
+
+        let error = Palette::parse("color0 #000\ncolor256 #000").unwrap_err();
+        assert_eq!(
+            error,
+            PaletteParseError {
+                kind: PaletteParseErrorKind::InvalidIndex("256".into()),
+                line: 2
+            }
+        );
+        let error = Palette::parse("color0 #00").unwrap_err();
+        assert_eq!(error.line, 1);
+        assert!(matches!(error.kind, PaletteParseErrorKind::InvalidColor(_)));
+    }
+
+    #[test]
+    fn test_palette_set() {
+        // Other tests run in parallel, so only bright white is changed, and only by a
+        // bit, which doesn't change any of their conversions.
+        let mut palette = Palette::XTERM;
+        palette.colors[15] = RgbColor::from(0xfffffe);
+        let white = Ansi256Color { index: 15 };
+        assert_eq!(white.as_rgb(), RgbColor::from(0xffffff));
+
+        palette_set(palette.clone());
+        let rgb = white.as_rgb();
+        let active = palette_get();
+        palette_set(Palette::XTERM);
+
+        assert_eq!(rgb, RgbColor::from(0xfffffe));
+        assert_eq!(active, palette);
+        assert_eq!(white.as_rgb(), RgbColor::from(0xffffff));
+    }
+
+    #[test]
+    fn test_load() {
+        let path = std::env::temp_dir().join(format!("r3bl_palette_{}.conf", std::process::id()));
+        std::fs::write(&path, "color4 #268bd2\n").unwrap();
+        let palette = Palette::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(palette.colors[4], RgbColor::from(0x268bd2));
+
+        assert!(matches!(Palette::load(&path), Err(PaletteLoadError::Io(_))));
+    }
+}
@@ -0,0 +1,652 @@
+/*
+ *   Copyright (c) 2023 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+//! Parses colors from strings (eg: in config files), using [FromStr] for [Color] and
+//! [RgbColor]. These forms are accepted (case insensitive, and surrounding whitespace is
+//! ignored):
+//! - `#rgb` and `#rrggbb`.
+//! - `rgb(1, 2, 3)`, where each component is `0..=255` or a percentage.
+//! - `hsl(210, 50%, 25%)`, where the hue is in degrees (an optional `deg` suffix is
+//!   allowed).
+//! - The 148 CSS named colors, eg: `rebeccapurple`.
+//! - `ansi(150)` or a bare `150`, which is an index into the ANSI 256 color palette.
+//! - `ansi16(9)`, which is one of the 16 system colors.
+//!
+//! [Display] for [Color] produces one of these forms, so it round trips.
+//!
+//! More info:
+//! - <https://www.w3.org/TR/css-color-4/#named-colors>
+
+use crate::*;
+use std::{
+    fmt::{Display, Formatter},
+    ops::Range,
+    str::FromStr,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColorParseError {
+    pub kind: ColorParseErrorKind,
+    /// Byte range in the input of the part that could not be parsed.
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColorParseErrorKind {
+    /// The input is empty (or only whitespace).
+    Empty,
+    /// A hex color doesn't have 3 or 6 digits.
+    InvalidHexLength,
+    /// A hex color contains something other than `0-9`, `a-f`.
+    InvalidHexDigit,
+    /// A number can't be parsed.
+    InvalidNumber,
+    /// A number is out of its allowed range, eg: `rgb(256, 0, 0)`.
+    OutOfRange,
+    /// The wrong number of arguments was passed to a function, eg: `rgb(1, 2)`.
+    WrongArgumentCount { expected: usize, actual: usize },
+    /// A function is missing its closing `)`.
+    MissingClosingParen,
+    /// Something other than whitespace follows the closing `)`.
+    TrailingCharacters,
+    /// A function other than `rgb`, `hsl`, `ansi`, `ansi16`.
+    UnknownFunction(String),
+    /// Not one of the CSS named colors.
+    UnknownColorName(String),
+}
+
+mod color_parse_error_impl {
+    use super::*;
+
+    impl Display for ColorParseError {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            let Range { start, end } = self.span;
+            match &self.kind {
+                ColorParseErrorKind::Empty => write!(f, "empty color"),
+                ColorParseErrorKind::InvalidHexLength => {
+                    write!(f, "hex color must have 3 or 6 digits, at {start}..{end}")
+                }
+                ColorParseErrorKind::InvalidHexDigit => {
+                    write!(f, "invalid hex digit at {start}..{end}")
+                }
+                ColorParseErrorKind::InvalidNumber => write!(f, "invalid number at {start}..{end}"),
+                ColorParseErrorKind::OutOfRange => {
+                    write!(f, "number out of range at {start}..{end}")
+                }
+                ColorParseErrorKind::WrongArgumentCount { expected, actual } => write!(
+                    f,
+                    "expected {expected} arguments, found {actual}, at {start}..{end}"
+                ),
+                ColorParseErrorKind::MissingClosingParen => {
+                    write!(f, "missing closing `)` at {start}..{end}")
+                }
+                ColorParseErrorKind::TrailingCharacters => {
+                    write!(f, "unexpected characters at {start}..{end}")
+                }
+                ColorParseErrorKind::UnknownFunction(name) => {
+                    write!(f, "unknown color function `{name}` at {start}..{end}")
+                }
+                ColorParseErrorKind::UnknownColorName(name) => {
+                    write!(f, "unknown color name `{name}` at {start}..{end}")
+                }
+            }
+        }
+    }
+
+    impl std::error::Error for ColorParseError {}
+}
+
+/// Parses `input` into a [Color]. Errors point into `input` (including any surrounding
+/// whitespace).
+pub fn parse_color(input: &str) -> Result<Color, ColorParseError> {
+    let start = input.len() - input.trim_start().len();
+    let trimmed = input.trim();
+    let span = start..start + trimmed.len();
+
+    if trimmed.is_empty() {
+        return Err(ColorParseError {
+            kind: ColorParseErrorKind::Empty,
+            span: 0..input.len(),
+        });
+    }
+
+    if let Some(hex) = trimmed.strip_prefix('#') {
+        return parse_hex(hex, span.start + 1).map(Color::from);
+    }
+
+    if trimmed.bytes().all(|it| it.is_ascii_digit()) {
+        let index = parse_integer(trimmed, span.clone(), 255)?;
+        return Ok(Color::Ansi256(index));
+    }
+
+    if let Some(open_paren) = trimmed.find('(') {
+        return parse_function(trimmed, open_paren, span.start);
+    }
+
+    match css_color_name_lookup(trimmed) {
+        Some(rgb) => Ok(Color::from(rgb)),
+        None => Err(ColorParseError {
+            kind: ColorParseErrorKind::UnknownColorName(trimmed.to_string()),
+            span,
+        }),
+    }
+}
+
+/// Returns the [RgbColor] for a CSS named color (case insensitive).
+pub fn css_color_name_lookup(name: &str) -> Option<RgbColor> {
+    let name = name.to_ascii_lowercase();
+    CSS_COLOR_NAMES
+        .binary_search_by(|(it, _)| (*it).cmp(name.as_str()))
+        .ok()
+        .map(|index| CSS_COLOR_NAMES[index].1.into())
+}
+
+/// `hex` is the part after the `#`, and `offset` is where it starts in the input.
+fn parse_hex(hex: &str, offset: usize) -> Result<RgbColor, ColorParseError> {
+    if let Some(index) = hex.find(|it: char| !it.is_ascii_hexdigit()) {
+        let len = hex[index..].chars().next().map_or(1, char::len_utf8);
+        return Err(ColorParseError {
+            kind: ColorParseErrorKind::InvalidHexDigit,
+            span: offset + index..offset + index + len,
+        });
+    }
+
+    let digit = |index: usize| u8::from_str_radix(&hex[index..index + 1], 16).unwrap_or_default();
+    match hex.len() {
+        3 => Ok(RgbColor {
+            red: digit(0) * 17,
+            green: digit(1) * 17,
+            blue: digit(2) * 17,
+        }),
+        6 => Ok(RgbColor {
+            red: digit(0) << 4 | digit(1),
+            green: digit(2) << 4 | digit(3),
+            blue: digit(4) << 4 | digit(5),
+        }),
+        _ => Err(ColorParseError {
+            kind: ColorParseErrorKind::InvalidHexLength,
+            span: offset - 1..offset + hex.len(),
+        }),
+    }
+}
+
+/// Parses `name(args)`, where `input` starts at `offset` in the original input.
+fn parse_function(input: &str, open_paren: usize, offset: usize) -> Result<Color, ColorParseError> {
+    let name = input[..open_paren].trim_end();
+    let Some(close_paren) = input.rfind(')') else {
+        return Err(ColorParseError {
+            kind: ColorParseErrorKind::MissingClosingParen,
+            span: offset + input.len()..offset + input.len(),
+        });
+    };
+    if close_paren + 1 != input.len() {
+        return Err(ColorParseError {
+            kind: ColorParseErrorKind::TrailingCharacters,
+            span: offset + close_paren + 1..offset + input.len(),
+        });
+    }
+
+    // Split the arguments on `,` or whitespace, keeping track of where each one is.
+    let args_start = open_paren + 1;
+    let mut args: Vec<(&str, Range<usize>)> = vec![];
+    let mut arg_start = None;
+    for (index, char) in input[args_start..close_paren].char_indices() {
+        let index = args_start + index;
+        let is_separator = char == ',' || char.is_whitespace();
+        match (is_separator, arg_start) {
+            (true, Some(start)) => {
+                args.push((&input[start..index], offset + start..offset + index));
+                arg_start = None;
+            }
+            (false, None) => arg_start = Some(index),
+            _ => {}
+        }
+    }
+    if let Some(start) = arg_start {
+        args.push((
+            &input[start..close_paren],
+            offset + start..offset + close_paren,
+        ));
+    }
+
+    let args_span = offset + args_start..offset + close_paren;
+    let expect_args = |expected: usize| {
+        if args.len() == expected {
+            Ok(())
+        } else {
+            Err(ColorParseError {
+                kind: ColorParseErrorKind::WrongArgumentCount {
+                    expected,
+                    actual: args.len(),
+                },
+                span: args_span.clone(),
+            })
+        }
+    };
+
+    match name.to_ascii_lowercase().as_str() {
+        "rgb" => {
+            expect_args(3)?;
+            let component = |(arg, span): &(&str, Range<usize>)| match arg.strip_suffix('%') {
+                Some(percent) => {
+                    let percent = parse_float(percent, span.clone(), 0.0..=100.0)?;
+                    Ok((percent * 2.55).round() as u8)
+                }
+                None => parse_integer(arg, span.clone(), 255),
+            };
+            Ok(Color::Rgb(
+                component(&args[0])?,
+                component(&args[1])?,
+                component(&args[2])?,
+            ))
+        }
+        "hsl" => {
+            expect_args(3)?;
+            let (hue, span) = &args[0];
+            let hue = parse_float(
+                hue.strip_suffix("deg").unwrap_or(hue),
+                span.clone(),
+                f32::MIN..=f32::MAX,
+            )?;
+            let percent = |(arg, span): &(&str, Range<usize>)| {
+                parse_float(
+                    arg.strip_suffix('%').unwrap_or(arg),
+                    span.clone(),
+                    0.0..=100.0,
+                )
+            };
+            let hsl = HslColor::new(hue, percent(&args[1])? / 100.0, percent(&args[2])? / 100.0);
+            Ok(Color::from(hsl))
+        }
+        "ansi" => {
+            expect_args(1)?;
+            let (arg, span) = &args[0];
+            Ok(Color::Ansi256(parse_integer(arg, span.clone(), 255)?))
+        }
+        "ansi16" => {
+            expect_args(1)?;
+            let (arg, span) = &args[0];
+            Ok(Color::Ansi16(parse_integer(arg, span.clone(), 15)?))
+        }
+        _ => Err(ColorParseError {
+            kind: ColorParseErrorKind::UnknownFunction(name.to_string()),
+            span: offset..offset + name.len(),
+        }),
+    }
+}
+
+fn parse_integer(input: &str, span: Range<usize>, max: u8) -> Result<u8, ColorParseError> {
+    let value: u32 = input.parse().map_err(|_| ColorParseError {
+        kind: ColorParseErrorKind::InvalidNumber,
+        span: span.clone(),
+    })?;
+    match u8::try_from(value) {
+        Ok(value) if value <= max => Ok(value),
+        _ => Err(ColorParseError {
+            kind: ColorParseErrorKind::OutOfRange,
+            span,
+        }),
+    }
+}
+
+fn parse_float(
+    input: &str,
+    span: Range<usize>,
+    range: std::ops::RangeInclusive<f32>,
+) -> Result<f32, ColorParseError> {
+    let value: f32 = match input.parse() {
+        Ok(value) if f32::is_finite(value) => value,
+        _ => {
+            return Err(ColorParseError {
+                kind: ColorParseErrorKind::InvalidNumber,
+                span,
+            })
+        }
+    };
+    if range.contains(&value) {
+        Ok(value)
+    } else {
+        Err(ColorParseError {
+            kind: ColorParseErrorKind::OutOfRange,
+            span,
+        })
+    }
+}
+
+mod from_str_impl {
+    use super::*;
+
+    impl FromStr for Color {
+        type Err = ColorParseError;
+
+        fn from_str(input: &str) -> Result<Self, Self::Err> {
+            parse_color(input)
+        }
+    }
+
+    /// ANSI colors are converted using the default xterm palette.
+    impl FromStr for RgbColor {
+        type Err = ColorParseError;
+
+        fn from_str(input: &str) -> Result<Self, Self::Err> {
+            parse_color(input).map(|it| it.as_rgb())
+        }
+    }
+}
+
+mod display_impl {
+    use super::*;
+
+    impl RgbColor {
+        /// Returns `#rrggbb`.
+        pub fn to_hex(&self) -> String {
+            format!("#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)
+        }
+    }
+
+    impl Color {
+        /// Returns `#rrggbb`. ANSI colors are converted using the default xterm palette.
+        pub fn to_hex(&self) -> String {
+            self.as_rgb().to_hex()
+        }
+    }
+
+    impl Display for RgbColor {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}", self.to_hex())
+        }
+    }
+
+    /// The output can be parsed back w/ [FromStr].
+    impl Display for Color {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Color::Rgb(red, green, blue) => write!(f, "#{red:02x}{green:02x}{blue:02x}"),
+                Color::Ansi256(index) => write!(f, "ansi({index})"),
+                Color::Ansi16(index) => write!(f, "ansi16({index})"),
+            }
+        }
+    }
+}
+
+/// Sorted by name, so it can be binary searched.
+#[rustfmt::skip]
+pub const CSS_COLOR_NAMES: [(&str, u32); 148] = [
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn error(input: &str) -> (ColorParseErrorKind, Range<usize>) {
+        let error = input.parse::<Color>().unwrap_err();
+        (error.kind, error.span)
+    }
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!("#5fafd7".parse(), Ok(Color::Rgb(0x5f, 0xaf, 0xd7)));
+        assert_eq!("#5FAFD7".parse(), Ok(Color::Rgb(0x5f, 0xaf, 0xd7)));
+        assert_eq!(" #f0a ".parse(), Ok(Color::Rgb(0xff, 0x00, 0xaa)));
+        assert_eq!("#12345".parse::<RgbColor>().unwrap_err().span, 0..6);
+        assert_eq!(
+            error("#12g456"),
+            (ColorParseErrorKind::InvalidHexDigit, 3..4)
+        );
+        assert_eq!(
+            error("#12345"),
+            (ColorParseErrorKind::InvalidHexLength, 0..6)
+        );
+    }
+
+    #[test]
+    fn test_parse_functions() {
+        assert_eq!("rgb(1,2,3)".parse(), Ok(Color::Rgb(1, 2, 3)));
+        assert_eq!("RGB( 1 2 3 )".parse(), Ok(Color::Rgb(1, 2, 3)));
+        assert_eq!("rgb(100%, 0%, 50%)".parse(), Ok(Color::Rgb(255, 0, 128)));
+        assert_eq!(
+            "hsl(210, 50%, 25%)".parse(),
+            Ok(Color::Rgb(0x20, 0x40, 0x60))
+        );
+        assert_eq!(
+            "hsl(210deg 50% 25%)".parse(),
+            Ok(Color::Rgb(0x20, 0x40, 0x60))
+        );
+        assert_eq!("ansi(150)".parse(), Ok(Color::Ansi256(150)));
+        assert_eq!("ansi16(9)".parse(), Ok(Color::Ansi16(9)));
+
+        assert_eq!(
+            error("rgb(1, 256, 3)"),
+            (ColorParseErrorKind::OutOfRange, 7..10)
+        );
+        assert_eq!(
+            error("rgb(1, x, 3)"),
+            (ColorParseErrorKind::InvalidNumber, 7..8)
+        );
+        assert_eq!(
+            error("rgb(1, 2)"),
+            (
+                ColorParseErrorKind::WrongArgumentCount {
+                    expected: 3,
+                    actual: 2
+                },
+                4..8
+            )
+        );
+        assert_eq!(
+            error("rgb(1, 2, 3"),
+            (ColorParseErrorKind::MissingClosingParen, 11..11)
+        );
+        assert_eq!(
+            error("rgb(1, 2, 3) x"),
+            (ColorParseErrorKind::TrailingCharacters, 12..14)
+        );
+        assert_eq!(
+            error("rgba(1, 2, 3)"),
+            (ColorParseErrorKind::UnknownFunction("rgba".into()), 0..4)
+        );
+        assert_eq!(error("ansi16(16)"), (ColorParseErrorKind::OutOfRange, 7..9));
+    }
+
+    #[test]
+    fn test_parse_names_and_indexes() {
+        assert_eq!(CSS_COLOR_NAMES.len(), 148);
+        assert!(CSS_COLOR_NAMES.windows(2).all(|it| it[0].0 < it[1].0));
+        for (name, rgb) in CSS_COLOR_NAMES {
+            assert_eq!(name.parse::<RgbColor>(), Ok(RgbColor::from(rgb)));
+        }
+        assert_eq!("RebeccaPurple".parse(), Ok(Color::Rgb(0x66, 0x33, 0x99)));
+
+        assert_eq!("150".parse(), Ok(Color::Ansi256(150)));
+        assert_eq!("150".parse(), Ok(RgbColor::from(0xafd787)));
+        assert_eq!(error("256"), (ColorParseErrorKind::OutOfRange, 0..3));
+        assert_eq!(
+            error(" notacolor"),
+            (
+                ColorParseErrorKind::UnknownColorName("notacolor".into()),
+                1..10
+            )
+        );
+        assert_eq!(error("  "), (ColorParseErrorKind::Empty, 0..2));
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        for color in [
+            Color::Rgb(0x5f, 0xaf, 0xd7),
+            Color::Ansi256(150),
+            Color::Ansi16(9),
+        ] {
+            assert_eq!(color.to_string().parse(), Ok(color));
+        }
+        assert_eq!(Color::Rgb(1, 2, 3).to_string(), "#010203");
+        assert_eq!(Color::Ansi256(196).to_hex(), "#ff0000");
+
+        let rgb = RgbColor::from(0x5fafd7);
+        assert_eq!(rgb.to_hex(), "#5fafd7");
+        assert_eq!(rgb.to_string().parse(), Ok(rgb));
+    }
+}
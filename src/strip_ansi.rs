@@ -0,0 +1,137 @@
+/*
+ *   Copyright (c) 2023 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+//! Remove ANSI escape sequences from a string, and measure how many terminal columns
+//! the remaining text occupies.
+//!
+//! More info:
+//! - <https://www.unicode.org/reports/tr11/>
+//! - <https://docs.rs/unicode-width/latest/unicode_width/>
+
+use crate::{recognize_escape, AnsiStyledText, OwnedStyledText, Recognized, ESC};
+use std::borrow::Cow;
+use unicode_width::UnicodeWidthStr;
+
+/// Removes all the escape sequences (SGR, cursor movement, OSC, etc) from `input`, using
+/// [recognize_escape]. An escape sequence that is cut off at the end of `input` is
+/// removed as well. If there is nothing to remove, then `input` is returned as is (w/out
+/// allocating).
+pub fn strip_ansi(input: &str) -> Cow<'_, str> {
+    let bytes = input.as_bytes();
+    if !bytes.contains(&ESC) {
+        return Cow::Borrowed(input);
+    }
+
+    let mut acc = String::with_capacity(input.len());
+    let mut text_start = 0;
+    let mut index = 0;
+
+    while index < bytes.len() {
+        if bytes[index] != ESC {
+            index += 1;
+            continue;
+        }
+
+        acc.push_str(&input[text_start..index]);
+        index += match recognize_escape(&bytes[index..]) {
+            Recognized::Complete { len, .. } | Recognized::Invalid { len } => len.max(1),
+            Recognized::Incomplete => bytes.len() - index,
+        };
+        text_start = index;
+    }
+    acc.push_str(&input[text_start..]);
+
+    Cow::Owned(acc)
+}
+
+/// Returns the number of terminal columns that `input` occupies once it is printed,
+/// ignoring any escape sequences in it. East Asian wide characters count as 2 columns,
+/// and combining marks and zero width joiners (eg: in emoji sequences) count as 0.
+pub fn visible_width(input: &str) -> usize {
+    strip_ansi(input).width()
+}
+
+mod visible_width_impl {
+    use super::*;
+
+    impl AnsiStyledText<'_> {
+        /// Returns the number of terminal columns that the text occupies, w/out the
+        /// escape sequences that are added when it is rendered.
+        pub fn visible_width(&self) -> usize {
+            visible_width(self.text)
+        }
+    }
+
+    impl OwnedStyledText {
+        /// Returns the number of terminal columns that the text occupies, w/out the
+        /// escape sequences that are added when it is rendered.
+        pub fn visible_width(&self) -> usize {
+            visible_width(&self.text)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color, ColorSupport, NoColorStyle, Style};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_strip_ansi() {
+        assert!(matches!(
+            strip_ansi("no escapes"),
+            Cow::Borrowed("no escapes")
+        ));
+        assert_eq!(strip_ansi("\x1b[1;38;5;150mHello\x1b[0m"), "Hello");
+        assert_eq!(
+            strip_ansi("a\x1b[2Kb\x1b]8;;https://r3bl.com\x07c\x1b(Bd"),
+            "abcd"
+        );
+        assert_eq!(strip_ansi("cut off \x1b[38;5"), "cut off ");
+    }
+
+    #[test]
+    fn test_unterminated_osc() {
+        let input = "\x1b]8;;\x1b[1mbold\x1b[0m tail";
+        assert_eq!(strip_ansi(input), "bold tail");
+        assert_eq!(visible_width(input), 9);
+    }
+
+    #[test]
+    fn test_visible_width() {
+        assert_eq!(visible_width("\x1b[1mabc\x1b[0m"), 3);
+        // East Asian wide characters.
+        assert_eq!(visible_width("\x1b[31m日本\x1b[0m"), 4);
+        // Combining acute accent.
+        assert_eq!(visible_width("e\u{301}"), 1);
+        // Family emoji (man, ZWJ, woman, ZWJ, girl).
+        assert_eq!(visible_width("👨\u{200d}👩\u{200d}👧"), 2);
+    }
+
+    #[test]
+    fn test_visible_width_of_rendered_text() {
+        let text = AnsiStyledText {
+            text: "Hello 世界",
+            style: &[Style::Bold, Style::Foreground(Color::Rgb(1, 2, 3))],
+        };
+        let rendered = text.to_string_with(ColorSupport::Truecolor, NoColorStyle::PlainText);
+        assert_eq!(rendered.len(), 33);
+        assert_eq!(visible_width(&rendered), 10);
+        assert_eq!(text.visible_width(), 10);
+    }
+}
@@ -0,0 +1,459 @@
+/*
+ *   Copyright (c) 2023 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::*;
+use std::ops::Range;
+
+/// A line that is made up of several differently styled spans. Unlike printing a bunch
+/// of [AnsiStyledText]s one after the other, rendering a `StyledLine` only emits the
+/// [SgrCode]s that are needed to go from the styles of one span to the next, and a
+/// single [SgrCode::Reset] at the end.
+///
+/// ## Example usage:
+///
+/// ```rust
+/// use r3bl_ansi_color::*;
+///
+/// let mut line = StyledLine::default();
+/// line.push("Error: ".styled().bold().fg(Color::Ansi16(1)));
+/// line.push("file not found ".styled().fg(Color::Ansi16(1)));
+/// line.push("(retrying)".styled());
+/// line.println();
+///
+/// assert_eq!(line.visible_width(), 32);
+/// let sliced = line.slice(0..5);
+/// assert_eq!(sliced.to_string_with(ColorSupport::NoColor, NoColorStyle::PlainText), "Error");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct StyledLine {
+    pub spans: Vec<OwnedStyledText>,
+}
+
+mod styled_line_impl {
+    use super::*;
+    use unicode_segmentation::UnicodeSegmentation;
+    use unicode_width::UnicodeWidthStr;
+
+    impl StyledLine {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn push(&mut self, span: impl Into<OwnedStyledText>) {
+            self.spans.push(span.into());
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.spans.iter().all(|span| span.text.is_empty())
+        }
+
+        /// Returns the number of terminal columns that the text of all the spans occupy.
+        pub fn visible_width(&self) -> usize {
+            self.spans.iter().map(|span| span.visible_width()).sum()
+        }
+
+        /// Returns the part of this line that fits in the given range of terminal
+        /// `columns`, keeping the styles of each span. A wide character (eg: `日`) that
+        /// only partially fits in the range is dropped.
+        pub fn slice(&self, columns: Range<usize>) -> StyledLine {
+            let mut acc = StyledLine::new();
+            let mut column = 0;
+
+            for span in &self.spans {
+                let mut text = String::new();
+                for grapheme in span.text.graphemes(true) {
+                    let start = column;
+                    column += grapheme.width();
+                    if start >= columns.start && column <= columns.end {
+                        text.push_str(grapheme);
+                    }
+                }
+                if !text.is_empty() {
+                    acc.push(OwnedStyledText {
+                        text,
+                        style: span.style.clone(),
+                    });
+                }
+            }
+
+            acc
+        }
+
+        pub fn println(&self) {
+            println!("{}", self);
+        }
+
+        pub fn print(&self) {
+            print!("{}", self);
+        }
+
+        /// Same as [AnsiStyledText::eprintln].
+        pub fn eprintln(&self) {
+            eprintln!("{}", self.to_string_for(Stream::Stderr));
+        }
+
+        /// Same as [AnsiStyledText::eprint].
+        pub fn eprint(&self) {
+            eprint!("{}", self.to_string_for(Stream::Stderr));
+        }
+
+        /// Same as [AnsiStyledText::write_to].
+        pub fn write_to(
+            &self,
+            writer: &mut impl std::io::Write,
+            color_support: ColorSupport,
+        ) -> std::io::Result<()> {
+            let string = self.to_string_with(color_support, NoColorStyle::default());
+            writer.write_all(string.as_bytes())
+        }
+
+        /// Same as [AnsiStyledText::to_string_for].
+        pub fn to_string_for(&self, stream: Stream) -> String {
+            self.to_string_with(query_runtime_color_support(stream), NoColorStyle::default())
+        }
+
+        /// Same as [AnsiStyledText::to_string_with].
+        pub fn to_string_with(
+            &self,
+            color_support: ColorSupport,
+            no_color_style: NoColorStyle,
+        ) -> String {
+            let mut acc = String::new();
+            // Writing to a String can't fail.
+            let _ = self.write_with(color_support, no_color_style, &mut acc);
+            acc
+        }
+
+        pub(crate) fn write_with(
+            &self,
+            color_support: ColorSupport,
+            no_color_style: NoColorStyle,
+            f: &mut impl std::fmt::Write,
+        ) -> std::fmt::Result {
+            let mut current = SgrState::default();
+
+            for span in self.spans.iter().filter(|span| !span.text.is_empty()) {
+                let next = SgrState::new(&span.style, color_support, no_color_style);
+                for sgr_code in current.transition_to(&next) {
+                    write!(f, "{}", sgr_code)?;
+                }
+                f.write_str(&span.text)?;
+                current = next;
+            }
+
+            if current != SgrState::default() {
+                write!(f, "{}", SgrCode::Reset)?;
+            }
+
+            Ok(())
+        }
+    }
+
+    impl From<Vec<OwnedStyledText>> for StyledLine {
+        fn from(spans: Vec<OwnedStyledText>) -> Self {
+            StyledLine { spans }
+        }
+    }
+
+    impl From<OwnedStyledText> for StyledLine {
+        fn from(span: OwnedStyledText) -> Self {
+            StyledLine { spans: vec![span] }
+        }
+    }
+
+    impl<T: Into<OwnedStyledText>> Extend<T> for StyledLine {
+        fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+            self.spans.extend(iter.into_iter().map(Into::into));
+        }
+    }
+
+    impl<T: Into<OwnedStyledText>> FromIterator<T> for StyledLine {
+        fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+            let mut acc = StyledLine::new();
+            acc.extend(iter);
+            acc
+        }
+    }
+
+    impl std::fmt::Display for StyledLine {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            self.write_with(
+                query_runtime_color_support(Stream::Stdout),
+                NoColorStyle::default(),
+                f,
+            )
+        }
+    }
+}
+
+/// The [SgrCode]s that are in effect at some point in a [StyledLine], after colors have
+/// been converted to fit the [ColorSupport]. So two [Color::Rgb]s that map to the same
+/// [Color::Ansi256] are considered to be the same.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct SgrState {
+    foreground: Option<SgrCode>,
+    background: Option<SgrCode>,
+    bold: bool,
+    dim: bool,
+    italic: bool,
+    underline: bool,
+    slow_blink: bool,
+    rapid_blink: bool,
+    invert: bool,
+    hidden: bool,
+    strikethrough: bool,
+    overline: bool,
+}
+
+mod sgr_state_impl {
+    use super::*;
+
+    impl SgrState {
+        pub fn new(
+            style: &[Style],
+            color_support: ColorSupport,
+            no_color_style: NoColorStyle,
+        ) -> Self {
+            let mut acc = SgrState::default();
+            for sgr_code in style
+                .iter()
+                .filter_map(|it| it.as_sgr_code(color_support, no_color_style))
+            {
+                match sgr_code {
+                    SgrCode::Bold => acc.bold = true,
+                    SgrCode::Dim => acc.dim = true,
+                    SgrCode::Italic => acc.italic = true,
+                    SgrCode::Underline => acc.underline = true,
+                    SgrCode::SlowBlink => acc.slow_blink = true,
+                    SgrCode::RapidBlink => acc.rapid_blink = true,
+                    SgrCode::Invert => acc.invert = true,
+                    SgrCode::Hidden => acc.hidden = true,
+                    SgrCode::Strikethrough => acc.strikethrough = true,
+                    SgrCode::Overline => acc.overline = true,
+                    SgrCode::ForegroundAnsi16(_)
+                    | SgrCode::ForegroundAnsi256(_)
+                    | SgrCode::ForegroundRGB(..) => acc.foreground = Some(sgr_code),
+                    SgrCode::BackgroundAnsi16(_)
+                    | SgrCode::BackgroundAnsi256(_)
+                    | SgrCode::BackgroundRGB(..) => acc.background = Some(sgr_code),
+                    _ => {}
+                }
+            }
+            acc
+        }
+
+        /// All the [SgrCode]s that have to be emitted to get to this state after a
+        /// [SgrCode::Reset].
+        fn sgr_codes(&self) -> Vec<SgrCode> {
+            let mut acc = self.attribute_sgr_codes_not_in(&SgrState::default());
+            acc.extend(self.foreground);
+            acc.extend(self.background);
+            acc
+        }
+
+        /// The attributes that are set in `self`, but not in `other`.
+        #[rustfmt::skip]
+        fn attribute_sgr_codes_not_in(&self, other: &SgrState) -> Vec<SgrCode> {
+            [
+                (self.bold          && !other.bold,          SgrCode::Bold),
+                (self.dim           && !other.dim,           SgrCode::Dim),
+                (self.italic        && !other.italic,        SgrCode::Italic),
+                (self.underline     && !other.underline,     SgrCode::Underline),
+                (self.slow_blink    && !other.slow_blink,    SgrCode::SlowBlink),
+                (self.rapid_blink   && !other.rapid_blink,   SgrCode::RapidBlink),
+                (self.invert        && !other.invert,        SgrCode::Invert),
+                (self.hidden        && !other.hidden,        SgrCode::Hidden),
+                (self.strikethrough && !other.strikethrough, SgrCode::Strikethrough),
+                (self.overline      && !other.overline,      SgrCode::Overline),
+            ]
+            .into_iter()
+            .filter_map(|(is_set, sgr_code)| is_set.then_some(sgr_code))
+            .collect()
+        }
+
+        /// Returns the shortest list of [SgrCode]s that changes the terminal from `self`
+        /// to `next`. This is either a diff of the 2 states, or a [SgrCode::Reset]
+        /// followed by everything in `next`.
+        pub fn transition_to(&self, next: &SgrState) -> Vec<SgrCode> {
+            if self == next {
+                return vec![];
+            }
+
+            let diff = self.diff_to(next);
+            let mut reset_then_set = vec![SgrCode::Reset];
+            reset_then_set.extend(next.sgr_codes());
+
+            let rendered_len =
+                |it: &[SgrCode]| it.iter().map(|code| code.to_string().len()).sum::<usize>();
+            if rendered_len(&reset_then_set) < rendered_len(&diff) {
+                reset_then_set
+            } else {
+                diff
+            }
+        }
+
+        fn diff_to(&self, next: &SgrState) -> Vec<SgrCode> {
+            let mut current = *self;
+            let mut acc = vec![];
+
+            // Turn off the attributes that aren't in `next`. Some of them share a code.
+            if (current.bold && !next.bold) || (current.dim && !next.dim) {
+                acc.push(SgrCode::ResetBoldDim);
+                (current.bold, current.dim) = (false, false);
+            }
+            if (current.slow_blink && !next.slow_blink)
+                || (current.rapid_blink && !next.rapid_blink)
+            {
+                acc.push(SgrCode::ResetBlink);
+                (current.slow_blink, current.rapid_blink) = (false, false);
+            }
+            for (is_unset, sgr_code) in [
+                (current.italic && !next.italic, SgrCode::ResetItalic),
+                (
+                    current.underline && !next.underline,
+                    SgrCode::ResetUnderline,
+                ),
+                (current.invert && !next.invert, SgrCode::ResetInvert),
+                (current.hidden && !next.hidden, SgrCode::ResetHidden),
+                (
+                    current.strikethrough && !next.strikethrough,
+                    SgrCode::ResetStrikethrough,
+                ),
+                (current.overline && !next.overline, SgrCode::ResetOverline),
+            ] {
+                if is_unset {
+                    acc.push(sgr_code);
+                }
+            }
+
+            // Turn on the attributes that are in `next`.
+            acc.extend(next.attribute_sgr_codes_not_in(&current));
+
+            // Change the colors.
+            if current.foreground != next.foreground {
+                acc.push(next.foreground.unwrap_or(SgrCode::ResetForeground));
+            }
+            if current.background != next.background {
+                acc.push(next.background.unwrap_or(SgrCode::ResetBackground));
+            }
+
+            acc
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn make_line() -> StyledLine {
+        [
+            "bold red".styled().bold().fg(Color::Ansi16(1)),
+            " red".styled().fg(Color::Ansi16(1)),
+            " red on blue"
+                .styled()
+                .fg(Color::Ansi16(1))
+                .bg(Color::Ansi16(4)),
+            " plain".styled(),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    fn test_render_only_transitions() {
+        assert_eq!(
+            make_line().to_string_with(ColorSupport::Ansi16, NoColorStyle::PlainText),
+            "\x1b[1m\x1b[31mbold red\x1b[22m red\x1b[44m red on blue\x1b[0m plain"
+        );
+    }
+
+    #[test]
+    fn test_render_single_reset_at_end() {
+        let mut line = StyledLine::new();
+        line.push("a".styled().italic().bold());
+        line.push("b".styled().italic());
+        line.push("c".styled().italic().dim());
+        assert_eq!(
+            line.to_string_with(ColorSupport::Ansi16, NoColorStyle::PlainText),
+            "\x1b[1m\x1b[3ma\x1b[22mb\x1b[2mc\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn test_render_colors_that_quantize_to_the_same_code() {
+        let mut line = StyledLine::new();
+        line.push("a".styled().fg(Color::Rgb(0, 0, 0)));
+        line.push(AnsiStyledText {
+            text: "b",
+            style: &[Style::Foreground(Color::Rgb(1, 1, 1))],
+        });
+        assert_eq!(
+            line.to_string_with(ColorSupport::Ansi256, NoColorStyle::PlainText),
+            "\x1b[38;5;16mab\x1b[0m"
+        );
+        assert_eq!(
+            line.to_string_with(ColorSupport::Truecolor, NoColorStyle::PlainText),
+            "\x1b[38;2;0;0;0ma\x1b[38;2;1;1;1mb\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn test_render_prefers_reset_when_shorter() {
+        let mut line = StyledLine::new();
+        line.push("a".styled().bold().italic().underline().strikethrough());
+        line.push("b".styled().overline());
+        assert_eq!(
+            line.to_string_with(ColorSupport::Ansi16, NoColorStyle::PlainText),
+            "\x1b[1m\x1b[3m\x1b[4m\x1b[9ma\x1b[0m\x1b[53mb\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn test_render_no_color() {
+        assert_eq!(
+            make_line().to_string_with(ColorSupport::NoColor, NoColorStyle::PlainText),
+            "bold red red red on blue plain"
+        );
+        assert_eq!(
+            make_line().to_string_with(ColorSupport::NoColor, NoColorStyle::KeepAttributes),
+            "\x1b[1mbold red\x1b[0m red red on blue plain"
+        );
+    }
+
+    #[test]
+    fn test_slice() {
+        let mut line = StyledLine::new();
+        line.push("ab".styled().bold());
+        line.push("日本".styled().italic());
+        line.push("cd".styled());
+        assert_eq!(line.visible_width(), 8);
+
+        assert_eq!(
+            line.slice(1..5),
+            StyledLine::from(vec!["b".styled().bold(), "日".styled().italic()])
+        );
+        // `日` occupies columns 2 and 3, so it is cut in half and dropped.
+        assert_eq!(
+            line.slice(3..7),
+            StyledLine::from(vec!["本".styled().italic(), "c".styled()])
+        );
+        assert_eq!(line.slice(5..100), StyledLine::from(vec!["cd".styled()]));
+        assert!(line.slice(8..10).is_empty());
+    }
+}
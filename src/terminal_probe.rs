@@ -0,0 +1,347 @@
+/*
+ *   Copyright (c) 2023 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+//! Asks the terminal which colors it supports, instead of guessing from the environment
+//! (which is often wrong over SSH, or in containers). This is opt-in, since it writes to
+//! the terminal and waits for the replies. These queries are sent:
+//! - `XTGETTCAP` for the `RGB`, `Tc` and `colors` terminfo capabilities, which the
+//!   terminal looks up in its own terminfo entry.
+//! - A truecolor SGR (`CSI 48 ; 2 ; 1 ; 2 ; 3 m`), followed by `DECRQSS` for the current
+//!   SGR. Terminals that support truecolor report the color back, others drop it. The
+//!   SGR is reset (`CSI 0 m`) right after.
+//! - DA1 (`CSI c`), which every terminal answers, and whose reply lists `22` if the
+//!   terminal supports ANSI colors. Since it is sent last, there is nothing left to wait
+//!   for once its reply arrives.
+//!
+//! Replies are only waited for until the timeout, so this never hangs, even if the
+//! terminal doesn't reply at all. Like [query_terminal_colors], this goes through
+//! [TerminalIo], so it can be tested w/ [ScriptedTerminal].
+//!
+//! ## Example usage:
+//!
+//! ```rust
+//! use r3bl_ansi_color::*;
+//! use std::time::Duration;
+//!
+//! let mut terminal = ScriptedTerminal::new()
+//!     .reply("\x1bP+q5463\x1b\\", "\x1bP1+r5463\x1b\\")
+//!     .reply("\x1b[c", "\x1b[?62;22c");
+//! let probed = probe_terminal_capabilities(&mut terminal, Duration::from_millis(100)).unwrap();
+//! assert_eq!(probed.color_support, Some(ColorSupport::Truecolor));
+//! assert!(probed.capabilities.contains(&TerminalCapability::Tc));
+//! ```
+//!
+//! More info:
+//! - <https://invisible-island.net/xterm/ctlseqs/ctlseqs.html#h3-Device-Control-functions>
+//! - <https://vt100.net/docs/vt510-rm/DECRQSS.html>
+//! - <https://github.com/termstandard/colors#querying-the-terminal>
+
+use crate::*;
+use std::{collections::BTreeSet, io, time::Duration};
+
+/// DECRQSS query for the current SGR.
+pub const DECRQSS_SGR_QUERY: &[u8] = b"\x1bP$qm\x1b\\";
+
+/// The truecolor SGR that is set before [DECRQSS_SGR_QUERY], and the reset after it.
+const TRUECOLOR_PROBE_SGR: &[u8] = b"\x1b[48;2;1;2;3m";
+const RESET_SGR: &[u8] = b"\x1b[0m";
+
+/// Something that a terminal reported about itself.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TerminalCapability {
+    /// The terminal replied to DA1.
+    DeviceAttributes,
+    /// The DA1 reply lists `22` (ANSI color).
+    AnsiColor,
+    /// `XTGETTCAP` reported the `RGB` capability.
+    Rgb,
+    /// `XTGETTCAP` reported the `Tc` capability.
+    Tc,
+    /// `DECRQSS` reported the truecolor SGR that was set.
+    TruecolorSgr,
+}
+
+/// What [probe_terminal_capabilities] found out.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TerminalCapabilities {
+    /// `None` if the replies don't say anything about colors (eg: there were no
+    /// replies). Then the environment is all there is to go by, see [supports_color].
+    pub color_support: Option<ColorSupport>,
+    pub capabilities: BTreeSet<TerminalCapability>,
+    /// The `colors` capability, reported by `XTGETTCAP`.
+    pub colors: Option<u32>,
+}
+
+mod terminal_capabilities_impl {
+    use super::*;
+
+    impl TerminalCapabilities {
+        pub fn has(&self, capability: TerminalCapability) -> bool {
+            self.capabilities.contains(&capability)
+        }
+    }
+}
+
+/// The `XTGETTCAP` query for the terminfo capability `name`, eg: `DCS + q 5463 ST` for
+/// `Tc`.
+pub fn xtgettcap_query(name: &str) -> Vec<u8> {
+    [
+        b"\x1bP+q",
+        hex_encode(name.as_bytes()).as_bytes(),
+        b"\x1b\\",
+    ]
+    .concat()
+}
+
+/// Sends the queries (see the [module docs](self)) to `terminal`, and waits at most
+/// `timeout` for the replies.
+pub fn probe_terminal_capabilities(
+    terminal: &mut impl TerminalIo,
+    timeout: Duration,
+) -> io::Result<TerminalCapabilities> {
+    let mut query = vec![];
+    for name in ["RGB", "Tc", "colors"] {
+        query.extend(xtgettcap_query(name));
+    }
+    query.extend_from_slice(TRUECOLOR_PROBE_SGR);
+    query.extend_from_slice(DECRQSS_SGR_QUERY);
+    query.extend_from_slice(RESET_SGR);
+    query.extend_from_slice(DA1_QUERY);
+    terminal.send(&query)?;
+
+    let mut acc = TerminalCapabilities::default();
+    read_replies(terminal, timeout, |sequence| match sequence {
+        EscapeSequence::Dcs {
+            kind: b'P',
+            payload,
+        } => {
+            apply_dcs_reply(&mut acc, payload);
+            false
+        }
+        EscapeSequence::Csi { params, .. } if is_da1_reply(&sequence) => {
+            acc.capabilities
+                .insert(TerminalCapability::DeviceAttributes);
+            if params[1..].split(|it| *it == b';').any(|it| it == b"22") {
+                acc.capabilities.insert(TerminalCapability::AnsiColor);
+            }
+            true
+        }
+        _ => false,
+    })?;
+
+    acc.color_support = resolve_color_support(&acc);
+    Ok(acc)
+}
+
+/// Same as [probe_terminal_capabilities], using the controlling terminal.
+#[cfg(all(unix, feature = "terminal-query"))]
+pub fn probe_tty_capabilities(timeout: Duration) -> io::Result<TerminalCapabilities> {
+    probe_terminal_capabilities(&mut TtyTerminal::open()?, timeout)
+}
+
+/// Handles the `XTGETTCAP` replies (eg: `1 + r 5463 ST`, or `1 + r 636f6c6f7273=323536
+/// ST`, where `1` means that the capability exists), and the `DECRQSS` reply (eg:
+/// `1 $ r 0;48:2::1:2:3 m`).
+fn apply_dcs_reply(acc: &mut TerminalCapabilities, payload: &[u8]) {
+    let Ok(payload) = std::str::from_utf8(payload) else {
+        return;
+    };
+
+    if let Some(capabilities) = payload.strip_prefix("1+r") {
+        // Some terminals reply to several capabilities at once.
+        for capability in capabilities.split(';') {
+            let (name, value) = match capability.split_once('=') {
+                Some((name, value)) => (hex_decode(name), hex_decode(value)),
+                None => (hex_decode(capability), Some(String::new())),
+            };
+            match (name.as_deref(), value) {
+                (Some("RGB"), _) => {
+                    acc.capabilities.insert(TerminalCapability::Rgb);
+                }
+                (Some("Tc"), _) => {
+                    acc.capabilities.insert(TerminalCapability::Tc);
+                }
+                (Some("colors"), Some(value)) => acc.colors = value.parse().ok(),
+                _ => {}
+            }
+        }
+    } else if let Some((_, sgr)) = payload.split_once("$r") {
+        // Both `;` and `:` separate the parameters, and the color space id is optional.
+        let sgr = sgr.strip_suffix('m').unwrap_or(sgr).replace(':', ";");
+        let fields: Vec<&str> = sgr.split(';').collect();
+        let reported = |color: &[&str]| fields.windows(color.len()).any(|it| it == color);
+        if reported(&["48", "2", "1", "2", "3"]) || reported(&["48", "2", "", "1", "2", "3"]) {
+            acc.capabilities.insert(TerminalCapability::TruecolorSgr);
+        }
+    }
+}
+
+/// Picks the [ColorSupport] from the [TerminalCapabilities::capabilities] and
+/// [TerminalCapabilities::colors].
+fn resolve_color_support(probed: &TerminalCapabilities) -> Option<ColorSupport> {
+    let colors = probed.colors.unwrap_or(0);
+    if probed.has(TerminalCapability::Rgb)
+        || probed.has(TerminalCapability::Tc)
+        || probed.has(TerminalCapability::TruecolorSgr)
+        || colors >= 1 << 24
+    {
+        Some(ColorSupport::Truecolor)
+    } else if colors >= 256 {
+        Some(ColorSupport::Ansi256)
+    } else if colors >= 8 || probed.has(TerminalCapability::AnsiColor) {
+        Some(ColorSupport::Ansi16)
+    } else {
+        None
+    }
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|it| format!("{it:02x}")).collect()
+}
+
+fn hex_decode(hex: &str) -> Option<String> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::time::Instant;
+
+    const TIMEOUT: Duration = Duration::from_millis(100);
+
+    fn probe(terminal: &mut ScriptedTerminal) -> TerminalCapabilities {
+        probe_terminal_capabilities(terminal, TIMEOUT).unwrap()
+    }
+
+    #[test]
+    fn test_xtgettcap_query() {
+        assert_eq!(xtgettcap_query("RGB"), b"\x1bP+q524742\x1b\\");
+        assert_eq!(hex_decode("636f6c6f7273"), Some("colors".to_string()));
+        assert_eq!(hex_decode("6"), None);
+        assert_eq!(hex_decode("zz"), None);
+    }
+
+    #[test]
+    fn test_truecolor_terminal() {
+        let mut terminal = ScriptedTerminal::new()
+            .reply(xtgettcap_query("RGB"), "\x1bP1+r524742=382f382f38\x1b\\")
+            .reply(xtgettcap_query("Tc"), "\x1bP0+r5463\x1b\\")
+            .reply(
+                xtgettcap_query("colors"),
+                "\x1bP1+r636f6c6f7273=3235360a\x1b\\",
+            )
+            .reply(DECRQSS_SGR_QUERY, "\x1bP1$r0;48:2::1:2:3m\x1b\\")
+            .reply(DA1_QUERY, "\x1b[?64;1;2;6;9;15;16;17;18;21;22;28c")
+            .with_chunk_size(3);
+        let probed = probe(&mut terminal);
+        assert_eq!(probed.color_support, Some(ColorSupport::Truecolor));
+        assert_eq!(
+            probed.capabilities,
+            BTreeSet::from([
+                TerminalCapability::DeviceAttributes,
+                TerminalCapability::AnsiColor,
+                TerminalCapability::Rgb,
+                TerminalCapability::TruecolorSgr,
+            ])
+        );
+        // `256\n` isn't a number.
+        assert_eq!(probed.colors, None);
+
+        // The SGR that was set is reset, and DA1 is sent last.
+        let sent = terminal.sent();
+        assert!(sent.ends_with(b"\x1b[48;2;1;2;3m\x1bP$qm\x1b\\\x1b[0m\x1b[c"));
+    }
+
+    #[test]
+    fn test_decrqss_alone_detects_truecolor() {
+        let mut terminal = ScriptedTerminal::new()
+            .reply(DECRQSS_SGR_QUERY, "\x1bP1$r48;2;1;2;3m\x1b\\")
+            .reply(DA1_QUERY, "\x1b[?62c");
+        let probed = probe(&mut terminal);
+        assert_eq!(probed.color_support, Some(ColorSupport::Truecolor));
+        assert!(probed.has(TerminalCapability::TruecolorSgr));
+        assert!(!probed.has(TerminalCapability::AnsiColor));
+    }
+
+    #[test]
+    fn test_256_and_16_color_terminals() {
+        // The truecolor SGR was dropped, so it isn't reported back.
+        let mut terminal = ScriptedTerminal::new()
+            .reply(xtgettcap_query("RGB"), "\x1bP0+r524742\x1b\\")
+            .reply(
+                xtgettcap_query("colors"),
+                "\x1bP1+r636f6c6f7273=323536\x1b\\",
+            )
+            .reply(DECRQSS_SGR_QUERY, "\x1bP1$r0m\x1b\\")
+            .reply(DA1_QUERY, "\x1b[?62;22c");
+        let probed = probe(&mut terminal);
+        assert_eq!(probed.color_support, Some(ColorSupport::Ansi256));
+        assert_eq!(probed.colors, Some(256));
+
+        let mut terminal = ScriptedTerminal::new().reply(DA1_QUERY, "\x1b[?62;22c");
+        assert_eq!(
+            probe(&mut terminal).color_support,
+            Some(ColorSupport::Ansi16)
+        );
+
+        // A DA1 reply w/out `22` doesn't say anything about colors.
+        let mut terminal = ScriptedTerminal::new().reply(DA1_QUERY, "\x1b[?1;2c");
+        let probed = probe(&mut terminal);
+        assert_eq!(probed.color_support, None);
+        assert!(probed.has(TerminalCapability::DeviceAttributes));
+    }
+
+    #[test]
+    fn test_no_replies() {
+        let probed = probe(&mut ScriptedTerminal::new());
+        assert_eq!(probed, TerminalCapabilities::default());
+    }
+
+    /// Keeps sending bytes that aren't replies, and never replies to DA1.
+    struct ChattyTerminal;
+
+    impl TerminalIo for ChattyTerminal {
+        fn send(&mut self, _bytes: &[u8]) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn receive(&mut self, buf: &mut [u8], _timeout: Duration) -> io::Result<usize> {
+            std::thread::sleep(Duration::from_millis(1));
+            let junk = b"x\x1b[?1;2$y";
+            let len = junk.len().min(buf.len());
+            buf[..len].copy_from_slice(&junk[..len]);
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn test_never_hangs() {
+        let start = Instant::now();
+        let probed = probe_terminal_capabilities(&mut ChattyTerminal, TIMEOUT).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert_eq!(probed.color_support, None);
+    }
+}
@@ -0,0 +1,493 @@
+/*
+ *   Copyright (c) 2023 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+//! Asks the terminal for the colors it actually uses, w/ these OSC queries:
+//! - `OSC 4 ; <index> ; ?` for a palette color.
+//! - `OSC 10 ; ?` and `OSC 11 ; ?` for the default foreground and background colors.
+//!
+//! The terminal replies w/ eg: `OSC 11 ; rgb:1e1e/1e1e/1e1e ST` on its input. Terminals
+//! that don't support a query simply don't reply, so a DA1 query (`CSI c`), which every
+//! terminal answers, is sent last. Once its reply arrives there is nothing left to wait
+//! for. There is also a timeout, in case the terminal doesn't reply at all.
+//!
+//! The terminal is accessed through [TerminalIo], so this can be tested w/
+//! [ScriptedTerminal] instead of a real terminal. `TtyTerminal` talks to the
+//! controlling TTY in raw mode, and it requires the `terminal-query` feature (on Unix).
+//!
+//! More info:
+//! - <https://invisible-island.net/xterm/ctlseqs/ctlseqs.html#h3-Operating-System-Commands>
+//! - <https://vt100.net/docs/vt510-rm/DA1.html>
+
+use crate::*;
+use std::{
+    collections::VecDeque,
+    io,
+    time::{Duration, Instant},
+};
+
+/// DA1 (Primary Device Attributes) query.
+pub const DA1_QUERY: &[u8] = b"\x1b[c";
+
+/// A connection to a terminal, that can send queries and receive the replies.
+pub trait TerminalIo {
+    fn send(&mut self, bytes: &[u8]) -> io::Result<()>;
+
+    /// Reads whatever is available into `buf`, waiting at most `timeout` for something
+    /// to arrive. Returns `0` if nothing arrived in time.
+    fn receive(&mut self, buf: &mut [u8], timeout: Duration) -> io::Result<usize>;
+}
+
+/// A fake terminal that plays back scripted replies, for tests. When a query that has a
+/// reply is sent, the reply is queued up to be received. Queries w/out a reply are
+/// ignored, just like a terminal that doesn't support them.
+///
+/// ## Example usage:
+///
+/// ```rust
+/// use r3bl_ansi_color::*;
+/// use std::time::Duration;
+///
+/// let mut terminal = ScriptedTerminal::new()
+///     .reply("\x1b]11;?\x1b\\", "\x1b]11;rgb:1e1e/1e1e/1e1e\x1b\\")
+///     .reply("\x1b[c", "\x1b[?62;22c");
+/// let colors = query_terminal_colors(&mut terminal, [], Duration::from_millis(100)).unwrap();
+/// assert_eq!(colors.background, Some(RgbColor::from(0x1e1e1e)));
+/// assert_eq!(colors.foreground, None);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedTerminal {
+    replies: Vec<(Vec<u8>, Vec<u8>)>,
+    pending: VecDeque<u8>,
+    sent: Vec<u8>,
+    chunk_size: Option<usize>,
+}
+
+mod scripted_terminal_impl {
+    use super::*;
+
+    impl ScriptedTerminal {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Replies w/ `reply` whenever `query` is sent.
+        pub fn reply(mut self, query: impl AsRef<[u8]>, reply: impl AsRef<[u8]>) -> Self {
+            self.replies
+                .push((query.as_ref().to_vec(), reply.as_ref().to_vec()));
+            self
+        }
+
+        /// Hands out at most `chunk_size` bytes per [TerminalIo::receive], to simulate
+        /// replies that are split across reads.
+        pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+            self.chunk_size = Some(chunk_size.max(1));
+            self
+        }
+
+        /// Everything that was sent to this terminal.
+        pub fn sent(&self) -> &[u8] {
+            &self.sent
+        }
+    }
+
+    impl TerminalIo for ScriptedTerminal {
+        fn send(&mut self, bytes: &[u8]) -> io::Result<()> {
+            self.sent.extend_from_slice(bytes);
+
+            // Reply in the order in which the queries were sent.
+            let mut replies: Vec<(usize, &[u8])> = self
+                .replies
+                .iter()
+                .filter_map(|(query, reply)| {
+                    bytes
+                        .windows(query.len())
+                        .position(|it| it == query.as_slice())
+                        .map(|position| (position, reply.as_slice()))
+                })
+                .collect();
+            replies.sort_by_key(|(position, _)| *position);
+            for (_, reply) in replies {
+                self.pending.extend(reply);
+            }
+
+            Ok(())
+        }
+
+        /// Never waits: returns `0` right away if there is nothing left to reply.
+        fn receive(&mut self, buf: &mut [u8], _timeout: Duration) -> io::Result<usize> {
+            let len = self
+                .pending
+                .len()
+                .min(buf.len())
+                .min(self.chunk_size.unwrap_or(usize::MAX));
+            for (index, byte) in self.pending.drain(..len).enumerate() {
+                buf[index] = byte;
+            }
+            Ok(len)
+        }
+    }
+}
+
+/// The colors that a terminal reported.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TerminalColors {
+    /// The default foreground color (`OSC 10`).
+    pub foreground: Option<RgbColor>,
+    /// The default background color (`OSC 11`).
+    pub background: Option<RgbColor>,
+    /// The palette colors that were reported (`OSC 4`), by index.
+    pub palette_colors: Vec<(u8, RgbColor)>,
+}
+
+mod terminal_colors_impl {
+    use super::*;
+
+    impl TerminalColors {
+        /// Returns [Palette::XTERM] w/ the reported palette colors filled in.
+        pub fn palette(&self) -> Palette {
+            let mut acc = Palette::XTERM;
+            for (index, color) in &self.palette_colors {
+                acc.colors[*index as usize] = *color;
+            }
+            acc
+        }
+    }
+}
+
+/// Queries the default foreground and background colors, and the palette colors at
+/// `palette_indices` (eg: `0..16` for the system colors). Whatever the terminal didn't
+/// report before the DA1 reply, or before `timeout`, is left out.
+pub fn query_terminal_colors(
+    terminal: &mut impl TerminalIo,
+    palette_indices: impl IntoIterator<Item = u8>,
+    timeout: Duration,
+) -> io::Result<TerminalColors> {
+    let mut query = b"\x1b]10;?\x1b\\\x1b]11;?\x1b\\".to_vec();
+    for index in palette_indices {
+        query.extend_from_slice(format!("\x1b]4;{index};?\x1b\\").as_bytes());
+    }
+    query.extend_from_slice(DA1_QUERY);
+    terminal.send(&query)?;
+
+    let mut acc = TerminalColors::default();
+    read_replies(terminal, timeout, |sequence| match sequence {
+        EscapeSequence::Osc { payload } => {
+            apply_color_reply(&mut acc, payload);
+            false
+        }
+        sequence => is_da1_reply(&sequence),
+    })?;
+    Ok(acc)
+}
+
+/// Receives from `terminal` until `on_sequence` returns `true` for one of the escape
+/// sequences in the replies, or until `timeout`. Anything other than escape sequences
+/// (eg: keys that the user typed) is ignored.
+pub fn read_replies(
+    terminal: &mut impl TerminalIo,
+    timeout: Duration,
+    mut on_sequence: impl FnMut(EscapeSequence<'_>) -> bool,
+) -> io::Result<()> {
+    let deadline = Instant::now() + timeout;
+    let mut pending: Vec<u8> = vec![];
+    let mut buf = [0; 1024];
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(());
+        }
+        let len = terminal.receive(&mut buf, remaining)?;
+        if len == 0 {
+            return Ok(());
+        }
+        pending.extend_from_slice(&buf[..len]);
+
+        let mut index = 0;
+        while let Some(offset) = pending[index..].iter().position(|it| *it == ESC) {
+            index += offset;
+            match recognize_escape(&pending[index..]) {
+                Recognized::Complete { sequence, len } => {
+                    if on_sequence(sequence) {
+                        return Ok(());
+                    }
+                    index += len;
+                }
+                Recognized::Invalid { len } => index += len.max(1),
+                Recognized::Incomplete => break,
+            }
+        }
+        if pending[index..].contains(&ESC) {
+            pending.drain(..index);
+        } else {
+            pending.clear();
+        }
+    }
+}
+
+/// DA1 replies look like `CSI ? 62 ; 22 c`.
+pub fn is_da1_reply(sequence: &EscapeSequence<'_>) -> bool {
+    matches!(
+        sequence,
+        EscapeSequence::Csi {
+            params: [b'?', ..],
+            final_byte: b'c',
+            ..
+        }
+    )
+}
+
+fn apply_color_reply(acc: &mut TerminalColors, payload: &[u8]) {
+    let Ok(payload) = std::str::from_utf8(payload) else {
+        return;
+    };
+    let mut fields = payload.splitn(3, ';');
+    match (fields.next(), fields.next(), fields.next()) {
+        (Some("10"), Some(spec), None) => acc.foreground = parse_x11_color_spec(spec),
+        (Some("11"), Some(spec), None) => acc.background = parse_x11_color_spec(spec),
+        (Some("4"), Some(index), Some(spec)) => {
+            if let (Ok(index), Some(color)) = (index.parse(), parse_x11_color_spec(spec)) {
+                acc.palette_colors.push((index, color));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Parses the `rgb:RRRR/GGGG/BBBB` color spec that terminals use in their replies, where
+/// each component has 1 to 4 hex digits. `rgba:RRRR/GGGG/BBBB/AAAA` (the alpha is
+/// ignored) and `#rrggbb` are accepted as well.
+pub fn parse_x11_color_spec(spec: &str) -> Option<RgbColor> {
+    if spec.starts_with('#') {
+        return spec.parse().ok();
+    }
+
+    let (components, count) = if let Some(components) = spec.strip_prefix("rgb:") {
+        (components, 3)
+    } else {
+        (spec.strip_prefix("rgba:")?, 4)
+    };
+
+    let components: Vec<&str> = components.split('/').collect();
+    if components.len() != count {
+        return None;
+    }
+    let component = |hex: &str| {
+        if !(1..=4).contains(&hex.len()) {
+            return None;
+        }
+        let value = u32::from_str_radix(hex, 16).ok()?;
+        let max = (1 << (4 * hex.len())) - 1;
+        Some(((value * 255 + max / 2) / max) as u8)
+    };
+
+    Some(RgbColor {
+        red: component(components[0])?,
+        green: component(components[1])?,
+        blue: component(components[2])?,
+    })
+}
+
+#[cfg(all(unix, feature = "terminal-query"))]
+pub use tty_terminal::*;
+
+#[cfg(all(unix, feature = "terminal-query"))]
+mod tty_terminal {
+    use super::*;
+    use std::{
+        fs::{File, OpenOptions},
+        io::{Read, Write},
+        os::fd::AsRawFd,
+    };
+
+    /// The controlling terminal (`/dev/tty`), in raw mode so that the replies can be read
+    /// right away, and aren't echoed. The original mode is restored on drop.
+    #[derive(Debug)]
+    pub struct TtyTerminal {
+        tty: File,
+        original_mode: libc::termios,
+    }
+
+    impl TtyTerminal {
+        pub fn open() -> io::Result<Self> {
+            let tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+            let fd = tty.as_raw_fd();
+
+            // SAFETY: `fd` is open for the lifetime of `tty`, and `termios` is only
+            // read after `tcgetattr` fills it in.
+            let original_mode = unsafe {
+                let mut termios = std::mem::zeroed::<libc::termios>();
+                if libc::tcgetattr(fd, &mut termios) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                termios
+            };
+
+            let mut raw_mode = original_mode;
+            raw_mode.c_lflag &= !(libc::ICANON | libc::ECHO);
+            raw_mode.c_cc[libc::VMIN] = 0;
+            raw_mode.c_cc[libc::VTIME] = 0;
+            // SAFETY: `fd` is open, and `raw_mode` is a valid `termios`.
+            if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw_mode) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(Self { tty, original_mode })
+        }
+    }
+
+    impl TerminalIo for TtyTerminal {
+        fn send(&mut self, bytes: &[u8]) -> io::Result<()> {
+            self.tty.write_all(bytes)?;
+            self.tty.flush()
+        }
+
+        fn receive(&mut self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+            let mut poll_fd = libc::pollfd {
+                fd: self.tty.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            let timeout = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+            // SAFETY: `poll_fd` is a single valid `pollfd`.
+            match unsafe { libc::poll(&mut poll_fd, 1, timeout) } {
+                0 => Ok(0),
+                result if result < 0 => Err(io::Error::last_os_error()),
+                _ => self.tty.read(buf),
+            }
+        }
+    }
+
+    impl Drop for TtyTerminal {
+        fn drop(&mut self) {
+            // SAFETY: `fd` is still open, and `original_mode` came from `tcgetattr`.
+            unsafe {
+                libc::tcsetattr(self.tty.as_raw_fd(), libc::TCSANOW, &self.original_mode);
+            }
+        }
+    }
+
+    /// Same as [query_terminal_colors], using the controlling terminal.
+    pub fn query_tty_colors(
+        palette_indices: impl IntoIterator<Item = u8>,
+        timeout: Duration,
+    ) -> io::Result<TerminalColors> {
+        query_terminal_colors(&mut TtyTerminal::open()?, palette_indices, timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const TIMEOUT: Duration = Duration::from_millis(100);
+
+    fn xterm() -> ScriptedTerminal {
+        ScriptedTerminal::new()
+            .reply("\x1b]10;?\x1b\\", "\x1b]10;rgb:d0d0/d0d0/d0d0\x1b\\")
+            .reply("\x1b]11;?\x1b\\", "\x1b]11;rgb:1e1e/1e1e/1e1e\x07")
+            .reply("\x1b]4;1;?\x1b\\", "\x1b]4;1;rgb:dc/32/2f\x1b\\")
+            .reply("\x1b]4;4;?\x1b\\", "\x1b]4;4;rgb:2/8/d\x1b\\")
+            .reply(DA1_QUERY, "\x1b[?64;1;2;6;22c")
+    }
+
+    #[test]
+    fn test_parse_x11_color_spec() {
+        assert_eq!(
+            parse_x11_color_spec("rgb:ffff/8080/0000"),
+            Some(RgbColor::from(0xff8000))
+        );
+        assert_eq!(
+            parse_x11_color_spec("rgb:ff/80/00"),
+            Some(RgbColor::from(0xff8000))
+        );
+        assert_eq!(
+            parse_x11_color_spec("rgb:f/8/0"),
+            Some(RgbColor::from(0xff8800))
+        );
+        assert_eq!(
+            parse_x11_color_spec("rgb:fff/800/000"),
+            Some(RgbColor::from(0xff8000))
+        );
+        assert_eq!(
+            parse_x11_color_spec("rgba:ffff/8080/0000/ffff"),
+            Some(RgbColor::from(0xff8000))
+        );
+        assert_eq!(
+            parse_x11_color_spec("#ff8000"),
+            Some(RgbColor::from(0xff8000))
+        );
+        assert_eq!(parse_x11_color_spec("rgb:ffff/8080"), None);
+        assert_eq!(parse_x11_color_spec("rgb:fffff/0/0"), None);
+        assert_eq!(parse_x11_color_spec("hsl:1/2/3"), None);
+    }
+
+    #[test]
+    fn test_query_terminal_colors() {
+        let mut terminal = xterm();
+        let colors = query_terminal_colors(&mut terminal, [1, 2, 4], TIMEOUT).unwrap();
+        assert_eq!(
+            colors,
+            TerminalColors {
+                foreground: Some(RgbColor::from(0xd0d0d0)),
+                background: Some(RgbColor::from(0x1e1e1e)),
+                palette_colors: vec![(1, RgbColor::from(0xdc322f)), (4, RgbColor::from(0x2288dd)),],
+            }
+        );
+        assert_eq!(colors.palette().colors[1], RgbColor::from(0xdc322f));
+        assert_eq!(colors.palette().colors[2], Palette::XTERM.colors[2]);
+        assert!(terminal.sent().ends_with(DA1_QUERY));
+    }
+
+    #[test]
+    fn test_replies_split_across_reads() {
+        for chunk_size in 1..8 {
+            let mut terminal = xterm().with_chunk_size(chunk_size);
+            let colors = query_terminal_colors(&mut terminal, [1, 4], TIMEOUT).unwrap();
+            assert_eq!(colors.background, Some(RgbColor::from(0x1e1e1e)));
+            assert_eq!(colors.palette_colors.len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_stops_at_the_da1_reply() {
+        // Anything after the DA1 reply is not read.
+        let mut terminal =
+            ScriptedTerminal::new().reply(DA1_QUERY, "\x1b[?62c\x1b]11;rgb:0/0/0\x1b\\");
+        let colors = query_terminal_colors(&mut terminal, [], TIMEOUT).unwrap();
+        assert_eq!(colors, TerminalColors::default());
+    }
+
+    #[test]
+    fn test_terminal_that_does_not_reply() {
+        let mut terminal = ScriptedTerminal::new();
+        let colors = query_terminal_colors(&mut terminal, 0..16, TIMEOUT).unwrap();
+        assert_eq!(colors, TerminalColors::default());
+    }
+
+    #[test]
+    fn test_ignores_typed_keys_and_other_sequences() {
+        let mut terminal = ScriptedTerminal::new().reply(
+            DA1_QUERY,
+            "ab\x1b[A\x1b]11;rgb:ffff/ffff/ffff\x1b\\\x1b]12;rgb:0/0/0\x1b\\\x1b[?1;2c",
+        );
+        let colors = query_terminal_colors(&mut terminal, [], TIMEOUT).unwrap();
+        assert_eq!(colors.background, Some(RgbColor::from(0xffffff)));
+        assert_eq!(colors.foreground, None);
+    }
+}
@@ -0,0 +1,552 @@
+/*
+ *   Copyright (c) 2023 R3BL LLC
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+//! Reads compiled terminfo entries (the files that `tic` writes), to find out how many
+//! colors a terminal supports. Both the legacy format (16 bit numbers) and the extended
+//! number format (32 bit numbers, used by ncurses 6.1+ eg: for `colors#0x1000000`) are
+//! supported, as well as the extended (user defined) capabilities, eg: `RGB`, `Tc` and
+//! `setrgbf`.
+//!
+//! ## Example usage:
+//!
+//! ```rust
+//! use r3bl_ansi_color::*;
+//!
+//! if let Ok(terminfo) = Terminfo::load("xterm-256color") {
+//!     println!("{:?} {:?}", terminfo.number("colors"), terminfo.color_support());
+//! }
+//! ```
+//!
+//! More info:
+//! - <https://man7.org/linux/man-pages/man5/term.5.html>
+//! - <https://man7.org/linux/man-pages/man5/terminfo.5.html>
+//! - <https://invisible-island.net/ncurses/man/user_caps.5.html>
+
+use crate::*;
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+    path::PathBuf,
+};
+
+const MAGIC_LEGACY: u16 = 0o432;
+const MAGIC_EXTENDED_NUMBERS: u16 = 0o1036;
+
+/// The positions of the standard capabilities that are used by this crate, in the
+/// numbers and strings sections (see `term.h`).
+#[rustfmt::skip]
+const STANDARD_NUMBERS: &[(&str, usize)] = &[
+    ("cols", 0), ("lines", 2), ("colors", 13), ("pairs", 14),
+];
+
+#[rustfmt::skip]
+const STANDARD_STRINGS: &[(&str, usize)] = &[
+    ("op", 297), ("setf", 302), ("setb", 303), ("setaf", 359), ("setab", 360),
+];
+
+/// A compiled terminfo entry.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Terminfo {
+    /// The names of the terminal, eg: `["xterm-256color", "xterm with 256 colors"]`.
+    pub names: Vec<String>,
+    numbers: Vec<Option<i32>>,
+    strings: Vec<Option<Vec<u8>>>,
+    extended_booleans: HashMap<String, bool>,
+    extended_numbers: HashMap<String, i32>,
+    extended_strings: HashMap<String, Vec<u8>>,
+}
+
+#[derive(Debug)]
+pub enum TerminfoError {
+    /// There's no entry for the terminal in any of the [terminfo_dirs_in].
+    NotFound(String),
+    Io(std::io::Error),
+    /// The file doesn't start w/ the legacy or the extended number format magic number.
+    InvalidMagic(u16),
+    /// The file is truncated, or a count or an offset in it is out of range.
+    Malformed,
+}
+
+mod terminfo_error_impl {
+    use super::*;
+
+    impl Display for TerminfoError {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            match self {
+                TerminfoError::NotFound(term) => write!(f, "no terminfo entry for `{term}`"),
+                TerminfoError::Io(error) => write!(f, "can't read terminfo entry: {error}"),
+                TerminfoError::InvalidMagic(magic) => {
+                    write!(f, "invalid terminfo magic number {magic:#o}")
+                }
+                TerminfoError::Malformed => write!(f, "malformed terminfo entry"),
+            }
+        }
+    }
+
+    impl std::error::Error for TerminfoError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            match self {
+                TerminfoError::Io(error) => Some(error),
+                _ => None,
+            }
+        }
+    }
+
+    impl From<std::io::Error> for TerminfoError {
+        fn from(error: std::io::Error) -> Self {
+            TerminfoError::Io(error)
+        }
+    }
+}
+
+/// The directories to look for terminfo entries in, in order: `$TERMINFO`,
+/// `~/.terminfo`, `$TERMINFO_DIRS` (where an empty entry means the system directories),
+/// and then the system directories.
+pub fn terminfo_dirs_in(env: &impl Environment) -> Vec<PathBuf> {
+    const SYSTEM_DIRS: &[&str] = &[
+        "/etc/terminfo",
+        "/lib/terminfo",
+        "/usr/share/terminfo",
+        "/usr/lib/terminfo",
+        "/usr/local/share/terminfo",
+    ];
+
+    let mut acc: Vec<PathBuf> = vec![];
+    let mut push = |dir: PathBuf| {
+        if !acc.contains(&dir) {
+            acc.push(dir);
+        }
+    };
+
+    if let Some(dir) = env.var("TERMINFO").filter(|it| !it.is_empty()) {
+        push(dir.into());
+    }
+    if let Some(home) = env.var("HOME").filter(|it| !it.is_empty()) {
+        push(PathBuf::from(home).join(".terminfo"));
+    }
+    for dir in env.var("TERMINFO_DIRS").unwrap_or_default().split(':') {
+        if dir.is_empty() {
+            SYSTEM_DIRS.iter().for_each(|it| push(it.into()));
+        } else {
+            push(dir.into());
+        }
+    }
+    SYSTEM_DIRS.iter().for_each(|it| push(it.into()));
+
+    acc
+}
+
+mod terminfo_impl {
+    use super::*;
+
+    impl Terminfo {
+        /// Finds and parses the entry for `term`, using the environment of this process.
+        pub fn load(term: &str) -> Result<Terminfo, TerminfoError> {
+            Self::load_in(&RealEnvironment, term)
+        }
+
+        /// Same as [Terminfo::load], but the directories come from `env` (see
+        /// [terminfo_dirs_in]).
+        pub fn load_in(env: &impl Environment, term: &str) -> Result<Terminfo, TerminfoError> {
+            let not_found = || TerminfoError::NotFound(term.to_string());
+
+            // Don't let `TERM` point outside of the terminfo directories.
+            let first_char = match term.chars().next() {
+                Some(it) if !term.contains('/') && it != '.' => it,
+                _ => return Err(not_found()),
+            };
+
+            // Most systems use the first character, but macOS uses its hex code.
+            let sub_dirs = [first_char.to_string(), format!("{:02x}", first_char as u32)];
+            for dir in terminfo_dirs_in(env) {
+                for sub_dir in &sub_dirs {
+                    let path = dir.join(sub_dir).join(term);
+                    if path.is_file() {
+                        return Terminfo::parse(&std::fs::read(path)?);
+                    }
+                }
+            }
+
+            Err(not_found())
+        }
+
+        /// Parses a compiled terminfo entry.
+        pub fn parse(bytes: &[u8]) -> Result<Terminfo, TerminfoError> {
+            let mut reader = Reader { bytes, pos: 0 };
+
+            let magic = reader.u16()?;
+            let number_size = match magic {
+                MAGIC_LEGACY => 2,
+                MAGIC_EXTENDED_NUMBERS => 4,
+                _ => return Err(TerminfoError::InvalidMagic(magic)),
+            };
+            let names_size = reader.count()?;
+            let boolean_count = reader.count()?;
+            let number_count = reader.count()?;
+            let string_count = reader.count()?;
+            let string_table_size = reader.count()?;
+
+            let names = c_str(reader.take(names_size)?, 0)?;
+            let names = String::from_utf8_lossy(names)
+                .split('|')
+                .map(str::to_string)
+                .collect();
+            // None of the standard booleans are used.
+            reader.take(boolean_count)?;
+            reader.align();
+            let numbers = reader.numbers(number_count, number_size)?;
+            let offsets = reader.offsets(string_count)?;
+            let string_table = reader.take(string_table_size)?;
+            let strings = strings(&offsets, string_table)?;
+
+            let mut acc = Terminfo {
+                names,
+                numbers,
+                strings,
+                ..Default::default()
+            };
+
+            // The extended capabilities are optional.
+            reader.align();
+            if reader.remaining() > 0 {
+                acc.parse_extended(&mut reader, number_size)?;
+            }
+
+            Ok(acc)
+        }
+
+        fn parse_extended(
+            &mut self,
+            reader: &mut Reader<'_>,
+            number_size: usize,
+        ) -> Result<(), TerminfoError> {
+            let boolean_count = reader.count()?;
+            let number_count = reader.count()?;
+            let string_count = reader.count()?;
+            let _string_table_items = reader.count()?;
+            let string_table_size = reader.count()?;
+
+            let booleans = reader.booleans(boolean_count)?;
+            reader.align();
+            let numbers = reader.numbers(number_count, number_size)?;
+            let offsets = reader.offsets(string_count)?;
+            let name_offsets = reader.offsets(boolean_count + number_count + string_count)?;
+            let string_table = reader.take(string_table_size)?;
+
+            let strings = strings(&offsets, string_table)?;
+            // The names come after the values in the string table.
+            let names_start = strings
+                .iter()
+                .zip(&offsets)
+                .filter_map(|(value, offset)| Some(*offset as usize + value.as_ref()?.len() + 1))
+                .max()
+                .unwrap_or(0);
+            let names = name_offsets
+                .iter()
+                .map(|offset| {
+                    let name = c_str(string_table, names_start + usize::try_from(*offset).ok()?);
+                    Some(String::from_utf8_lossy(name.ok()?).into_owned())
+                })
+                .collect::<Option<Vec<_>>>()
+                .ok_or(TerminfoError::Malformed)?;
+
+            let (boolean_names, rest) = names.split_at(boolean_count);
+            let (number_names, string_names) = rest.split_at(number_count);
+            self.extended_booleans = boolean_names.iter().cloned().zip(booleans).collect();
+            self.extended_numbers = number_names
+                .iter()
+                .cloned()
+                .zip(numbers)
+                .filter_map(|(name, value)| Some((name, value?)))
+                .collect();
+            self.extended_strings = string_names
+                .iter()
+                .cloned()
+                .zip(strings)
+                .filter_map(|(name, value)| Some((name, value?)))
+                .collect();
+
+            Ok(())
+        }
+
+        /// Whether the extended boolean capability `name` (eg: `Tc`) is present.
+        pub fn flag(&self, name: &str) -> bool {
+            self.extended_booleans.get(name).copied().unwrap_or(false)
+        }
+
+        /// The value of the numeric capability `name` (eg: `colors`).
+        pub fn number(&self, name: &str) -> Option<i32> {
+            match STANDARD_NUMBERS.iter().find(|(it, _)| *it == name) {
+                Some((_, index)) => self.numbers.get(*index).copied().flatten(),
+                None => self.extended_numbers.get(name).copied(),
+            }
+        }
+
+        /// The value of the string capability `name` (eg: `setaf`).
+        pub fn string(&self, name: &str) -> Option<&[u8]> {
+            match STANDARD_STRINGS.iter().find(|(it, _)| *it == name) {
+                Some((_, index)) => self.strings.get(*index)?.as_deref(),
+                None => self.extended_strings.get(name).map(Vec::as_slice),
+            }
+        }
+
+        /// The colors that the terminal supports, or `None` if it doesn't support any:
+        /// - Truecolor if it has `RGB` (as a boolean, number, or string), `Tc`,
+        ///   `setrgbf`, or `colors#0x1000000`.
+        /// - ANSI 256 if it has at least 256 `colors`.
+        /// - ANSI 16 if it has at least 8 `colors`, and `setaf` or `setf`.
+        pub fn color_support(&self) -> Option<ColorSupport> {
+            let colors = self.number("colors").unwrap_or(0);
+            if self.flag("RGB")
+                || self.number("RGB").is_some()
+                || self.string("RGB").is_some()
+                || self.flag("Tc")
+                || self.string("setrgbf").is_some()
+                || colors >= 1 << 24
+            {
+                Some(ColorSupport::Truecolor)
+            } else if colors >= 256 {
+                Some(ColorSupport::Ansi256)
+            } else if colors >= 8
+                && (self.string("setaf").is_some() || self.string("setf").is_some())
+            {
+                Some(ColorSupport::Ansi16)
+            } else {
+                None
+            }
+        }
+    }
+
+    fn c_str(table: &[u8], offset: usize) -> Result<&[u8], TerminfoError> {
+        let rest = table.get(offset..).ok_or(TerminfoError::Malformed)?;
+        let end = rest.iter().position(|it| *it == 0).unwrap_or(rest.len());
+        Ok(&rest[..end])
+    }
+
+    /// Absent (`-1`) and cancelled (`-2`) strings have negative offsets.
+    fn strings(offsets: &[i16], table: &[u8]) -> Result<Vec<Option<Vec<u8>>>, TerminfoError> {
+        offsets
+            .iter()
+            .map(|offset| match usize::try_from(*offset) {
+                Ok(offset) => Ok(Some(c_str(table, offset)?.to_vec())),
+                Err(_) => Ok(None),
+            })
+            .collect()
+    }
+
+    /// Reads little endian values, and keeps track of the position so sections can be
+    /// aligned to an even offset.
+    struct Reader<'a> {
+        bytes: &'a [u8],
+        pos: usize,
+    }
+
+    impl<'a> Reader<'a> {
+        fn take(&mut self, len: usize) -> Result<&'a [u8], TerminfoError> {
+            let it = self
+                .bytes
+                .get(self.pos..self.pos + len)
+                .ok_or(TerminfoError::Malformed)?;
+            self.pos += len;
+            Ok(it)
+        }
+
+        fn remaining(&self) -> usize {
+            self.bytes.len().saturating_sub(self.pos)
+        }
+
+        fn align(&mut self) {
+            if self.pos % 2 == 1 && self.remaining() > 0 {
+                self.pos += 1;
+            }
+        }
+
+        fn u16(&mut self) -> Result<u16, TerminfoError> {
+            let it = self.take(2)?;
+            Ok(u16::from_le_bytes([it[0], it[1]]))
+        }
+
+        fn i16(&mut self) -> Result<i16, TerminfoError> {
+            Ok(self.u16()? as i16)
+        }
+
+        /// A count in a header, which can't be negative.
+        fn count(&mut self) -> Result<usize, TerminfoError> {
+            usize::try_from(self.i16()?).map_err(|_| TerminfoError::Malformed)
+        }
+
+        /// Cancelled booleans are `-2`.
+        fn booleans(&mut self, count: usize) -> Result<Vec<bool>, TerminfoError> {
+            Ok(self.take(count)?.iter().map(|it| *it == 1).collect())
+        }
+
+        /// Absent (`-1`) and cancelled (`-2`) numbers are negative.
+        fn numbers(
+            &mut self,
+            count: usize,
+            size: usize,
+        ) -> Result<Vec<Option<i32>>, TerminfoError> {
+            (0..count)
+                .map(|_| {
+                    let number = match size {
+                        2 => self.i16()? as i32,
+                        _ => {
+                            let it = self.take(4)?;
+                            i32::from_le_bytes([it[0], it[1], it[2], it[3]])
+                        }
+                    };
+                    Ok((number >= 0).then_some(number))
+                })
+                .collect()
+        }
+
+        fn offsets(&mut self, count: usize) -> Result<Vec<i16>, TerminfoError> {
+            (0..count).map(|_| self.i16()).collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    // Copied from the ncurses terminfo database, except for `r3bl-truecolor`, which is
+    // compiled from `r3bl-truecolor.src` w/ `tic -x -o tests/fixtures/terminfo`.
+    const XTERM: &[u8] = include_bytes!("../tests/fixtures/terminfo/x/xterm");
+    const XTERM_256COLOR: &[u8] = include_bytes!("../tests/fixtures/terminfo/x/xterm-256color");
+    const XTERM_DIRECT: &[u8] = include_bytes!("../tests/fixtures/terminfo/x/xterm-direct");
+    const VT100: &[u8] = include_bytes!("../tests/fixtures/terminfo/v/vt100");
+    const R3BL_TRUECOLOR: &[u8] = include_bytes!("../tests/fixtures/terminfo/r/r3bl-truecolor");
+
+    fn fixtures_dir() -> String {
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/terminfo").to_string()
+    }
+
+    #[test]
+    fn test_parse_legacy_format() {
+        let terminfo = Terminfo::parse(XTERM).unwrap();
+        assert_eq!(terminfo.names[0], "xterm");
+        assert_eq!(terminfo.number("colors"), Some(8));
+        assert_eq!(terminfo.number("pairs"), Some(64));
+        assert_eq!(terminfo.number("cols"), Some(80));
+        assert_eq!(terminfo.string("setaf"), Some(&b"\x1b[3%p1%dm"[..]));
+        assert_eq!(terminfo.string("op"), Some(&b"\x1b[39;49m"[..]));
+        assert_eq!(terminfo.color_support(), Some(ColorSupport::Ansi16));
+
+        let terminfo = Terminfo::parse(VT100).unwrap();
+        assert_eq!(terminfo.names[0], "vt100");
+        assert_eq!(terminfo.number("colors"), None);
+        assert_eq!(terminfo.string("setaf"), None);
+        assert_eq!(terminfo.color_support(), None);
+    }
+
+    #[test]
+    fn test_parse_extended_number_format() {
+        let terminfo = Terminfo::parse(XTERM_256COLOR).unwrap();
+        assert_eq!(terminfo.names[0], "xterm-256color");
+        assert_eq!(terminfo.number("colors"), Some(256));
+        assert_eq!(terminfo.number("pairs"), Some(0x10000));
+        assert!(!terminfo.flag("RGB"));
+        assert_eq!(terminfo.color_support(), Some(ColorSupport::Ansi256));
+
+        let terminfo = Terminfo::parse(XTERM_DIRECT).unwrap();
+        assert_eq!(terminfo.number("colors"), Some(0x1000000));
+        assert!(terminfo.flag("RGB"));
+        assert_eq!(terminfo.color_support(), Some(ColorSupport::Truecolor));
+    }
+
+    #[test]
+    fn test_parse_extended_capabilities() {
+        let terminfo = Terminfo::parse(R3BL_TRUECOLOR).unwrap();
+        assert_eq!(
+            terminfo.names,
+            vec![
+                "r3bl-truecolor",
+                "fixture w/ the Tc and setrgbf extended capabilities"
+            ]
+        );
+        assert!(terminfo.flag("Tc"));
+        assert!(!terminfo.flag("RGB"));
+        assert_eq!(
+            terminfo.string("setrgbf"),
+            Some(&b"\x1b[38;2;%p1%d;%p2%d;%p3%dm"[..])
+        );
+        assert_eq!(
+            terminfo.string("setrgbb"),
+            Some(&b"\x1b[48;2;%p1%d;%p2%d;%p3%dm"[..])
+        );
+        assert_eq!(terminfo.color_support(), Some(ColorSupport::Truecolor));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(
+            Terminfo::parse(b"\x1e\x03"),
+            Err(TerminfoError::InvalidMagic(0o1436))
+        ));
+        assert!(matches!(
+            Terminfo::parse(&XTERM[..100]),
+            Err(TerminfoError::Malformed)
+        ));
+        assert!(matches!(
+            Terminfo::parse(&[]),
+            Err(TerminfoError::Malformed)
+        ));
+    }
+
+    #[test]
+    fn test_load_in() {
+        let env = FakeEnvironment::new().var("TERMINFO", &fixtures_dir());
+        let terminfo = Terminfo::load_in(&env, "xterm-direct").unwrap();
+        assert_eq!(terminfo.names[0], "xterm-direct");
+
+        assert!(matches!(
+            Terminfo::load_in(&env, "../x/xterm"),
+            Err(TerminfoError::NotFound(_))
+        ));
+        let env = FakeEnvironment::new().var("TERMINFO_DIRS", "/nonexistent");
+        assert!(matches!(
+            Terminfo::load_in(&env, "r3bl-truecolor"),
+            Err(TerminfoError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_terminfo_dirs_in() {
+        let env = FakeEnvironment::new()
+            .var("TERMINFO", "/a")
+            .var("HOME", "/home/r3bl")
+            .var("TERMINFO_DIRS", "/b::/c");
+        let dirs = terminfo_dirs_in(&env);
+        let dirs: Vec<_> = dirs.iter().map(|it| it.to_str().unwrap()).collect();
+        assert_eq!(
+            dirs,
+            vec![
+                "/a",
+                "/home/r3bl/.terminfo",
+                "/b",
+                "/etc/terminfo",
+                "/lib/terminfo",
+                "/usr/share/terminfo",
+                "/usr/lib/terminfo",
+                "/usr/local/share/terminfo",
+                "/c",
+            ]
+        );
+    }
+}